@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use crate::disassembler::DisassembleOptions;
+use crate::emulator::{RAM_SIZE, ROM_SIZE};
+use crate::output::format::{find_format, Format, FormatOptions, HackText};
+use crate::parser::compiler::CompileOptions;
+use crate::parser::dialect::Dialect;
+use crate::parser::table::DestStyle;
+use crate::preprocessor::condition::evaluate;
+
+pub enum Command {
+    Help,
+    Assemble(AssembleArgs),
+    Disassemble(DisassembleArgs),
+    Run(RunArgs),
+    Test(Vec<PathBuf>),
+    Translate(TranslateArgs),
+    Link(LinkArgs),
+}
+
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+pub enum Output {
+    Stdout,
+    File(PathBuf),
+}
+
+pub struct AssembleArgs {
+    pub inputs: Vec<Input>,
+    pub output: Option<Output>,
+    pub options: CompileOptions,
+    pub format: Box<dyn Format>,
+    pub format_options: FormatOptions,
+    pub listing: bool,
+    pub symbols: bool,
+    pub symbols_json: bool,
+    pub source_map: bool,
+    // Write a relocatable `.hobj` module instead of the program.
+    pub object: bool,
+}
+
+pub struct DisassembleArgs {
+    pub input: Input,
+    pub output: Option<Output>,
+    pub options: DisassembleOptions,
+}
+
+pub struct RunArgs {
+    pub input: Input,
+    pub options: CompileOptions,
+    pub max_cycles: u64,
+    pub ranges: Vec<(usize, usize)>,
+    pub presets: Vec<(usize, u16)>,
+}
+
+pub struct LinkArgs {
+    pub inputs: Vec<PathBuf>,
+    pub output: Option<Output>,
+    pub format: Box<dyn Format>,
+    pub format_options: FormatOptions,
+}
+
+pub struct TranslateArgs {
+    pub input: PathBuf,
+    pub output: Option<Output>,
+    // Defaults to on for directories and off for single `.vm` files.
+    pub bootstrap: Option<bool>,
+    pub assemble: bool,
+}
+
+impl Input {
+    pub fn name(&self) -> String {
+        match self {
+            Input::Stdin => String::from("<stdin>"),
+            Input::File(path) => path.display().to_string(),
+        }
+    }
+}
+
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    match args.first().map(|arg| arg.as_str()) {
+        Some("disasm") => parse_disassemble(&args[1..]),
+        Some("run") => parse_run(&args[1..]),
+        Some("test") => parse_test(&args[1..]),
+        Some("vm") => parse_translate(&args[1..]),
+        Some("link") => parse_link(&args[1..]),
+        _ => parse_assemble(args),
+    }
+}
+
+fn parse_assemble(args: &[String]) -> Result<Command, String> {
+    let mut inputs: Vec<Input> = vec![];
+    let mut output: Option<Output> = None;
+    let mut options = CompileOptions::default();
+    let mut format: Box<dyn Format> = Box::new(HackText);
+    let mut format_options = FormatOptions::default();
+    let mut listing = false;
+    let mut symbols = false;
+    let mut symbols_json = false;
+    let mut source_map = false;
+    let mut object = false;
+    let mut only_inputs = false;
+    let mut i = 0;
+
+    while i < args.len() {
+        let arg = args[i].as_str();
+        match arg {
+            _ if only_inputs => inputs.push(parse_input(arg)),
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" | "--output" => {
+                let value = next_value(args, &mut i)?;
+                if output.is_some() {
+                    return Err(String::from("`-o` given more than once"));
+                }
+                output = Some(parse_output(value));
+            },
+            "--max-errors" => options.max_errors = parse_number(arg, next_value(args, &mut i)?)?,
+            "--dialect" => options.dialect = match next_value(args, &mut i)? {
+                "strict" => Dialect::Strict,
+                "permissive" => Dialect::Permissive,
+                dialect => return Err(format!("unknown dialect `{}`, expected `strict` or `permissive`", dialect)),
+            },
+            "-D" | "--define" => options.defines.push(parse_define(next_value(args, &mut i)?)?),
+            "-I" | "--include-path" => options.include_paths.push(PathBuf::from(next_value(args, &mut i)?)),
+            "-f" | "--format" => {
+                let name = next_value(args, &mut i)?;
+                format = find_format(name).ok_or_else(|| format!("unknown output format `{}`", name))?;
+            },
+            "--rom-size" => format_options.rom_size = parse_number(arg, next_value(args, &mut i)?)?,
+            "--listing" => listing = true,
+            "--symbols" => symbols = true,
+            "--symbols-json" => symbols_json = true,
+            "--source-map" => source_map = true,
+            "--object" => object = true,
+            "--" => only_inputs = true,
+            _ if arg.starts_with('-') && arg != "-" => return Err(format!("unknown option `{}`", arg)),
+            _ => inputs.push(parse_input(arg)),
+        }
+        i += 1;
+    }
+
+    if inputs.is_empty() {
+        return Err(String::from("no input files"));
+    }
+    if inputs.len() > 1 && output.is_some() {
+        return Err(String::from("`-o` cannot be used with multiple inputs"));
+    }
+    if inputs.iter().filter(|input| matches!(input, Input::Stdin)).count() > 1 {
+        return Err(String::from("standard input given more than once"));
+    }
+
+    if format_options.rom_size == 0 || format_options.rom_size > ROM_SIZE {
+        return Err(format!("`--rom-size` must be between 1 and {}", ROM_SIZE));
+    }
+
+    options.relocatable = object;
+    Ok(Command::Assemble(AssembleArgs {
+        inputs, output, options, format, format_options, listing, symbols, symbols_json, source_map, object
+    }))
+}
+
+// `NAME=VALUE`, or `NAME` alone for 1.
+fn parse_define(value: &str) -> Result<(String, i64), String> {
+    let (name, number) = match value.find('=') {
+        Some(index) => (&value[..index], &value[index + 1..]),
+        None => (value, "1"),
+    };
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) || !name.chars().all(|c| c.is_alphanumeric() || "_.$".contains(c)) {
+        return Err(format!("invalid name `{}` for `-D`", name));
+    }
+    let number = evaluate(number, &HashMap::new()).map_err(|_| format!("invalid value `{}` for `-D`", number))?;
+    Ok((String::from(name), number))
+}
+
+fn parse_link(args: &[String]) -> Result<Command, String> {
+    let mut inputs: Vec<PathBuf> = vec![];
+    let mut output: Option<Output> = None;
+    let mut format: Box<dyn Format> = Box::new(HackText);
+    let mut format_options = FormatOptions::default();
+    let mut i = 0;
+
+    while i < args.len() {
+        let arg = args[i].as_str();
+        match arg {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" | "--output" => output = Some(parse_output(next_value(args, &mut i)?)),
+            "-f" | "--format" => {
+                let name = next_value(args, &mut i)?;
+                format = find_format(name).ok_or_else(|| format!("unknown output format `{}`", name))?;
+            },
+            "--rom-size" => format_options.rom_size = parse_number(arg, next_value(args, &mut i)?)?,
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+        i += 1;
+    }
+
+    if inputs.is_empty() {
+        return Err(String::from("no object files"));
+    }
+    if format_options.rom_size == 0 || format_options.rom_size > ROM_SIZE {
+        return Err(format!("`--rom-size` must be between 1 and {}", ROM_SIZE));
+    }
+    Ok(Command::Link(LinkArgs { inputs, output, format, format_options }))
+}
+
+fn parse_disassemble(args: &[String]) -> Result<Command, String> {
+    let mut input: Option<Input> = None;
+    let mut output: Option<Output> = None;
+    let mut options = DisassembleOptions::default();
+    let mut i = 0;
+
+    while i < args.len() {
+        let arg = args[i].as_str();
+        match arg {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" | "--output" => output = Some(parse_output(next_value(args, &mut i)?)),
+            "--no-labels" => options.labels = false,
+            "--no-symbols" => options.symbols = false,
+            "--dest-style" => options.dest_style = match next_value(args, &mut i)? {
+                "classic" => DestStyle::Classic,
+                "modern" => DestStyle::Modern,
+                style => return Err(format!("unknown dest style `{}`, expected `classic` or `modern`", style)),
+            },
+            _ if arg.starts_with('-') && arg != "-" => return Err(format!("unknown option `{}`", arg)),
+            _ if input.is_some() => return Err(String::from("`disasm` takes a single input")),
+            _ => input = Some(parse_input(arg)),
+        }
+        i += 1;
+    }
+
+    let input = input.ok_or_else(|| String::from("no input file"))?;
+    Ok(Command::Disassemble(DisassembleArgs { input, output, options }))
+}
+
+fn parse_run(args: &[String]) -> Result<Command, String> {
+    let mut input: Option<Input> = None;
+    let mut max_cycles: u64 = 1_000_000;
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    let mut presets: Vec<(usize, u16)> = vec![];
+    let mut options = CompileOptions::default();
+    let mut i = 0;
+
+    while i < args.len() {
+        let arg = args[i].as_str();
+        match arg {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--cycles" => max_cycles = parse_number(arg, next_value(args, &mut i)?)? as u64,
+            "--ram" => ranges.push(parse_range(next_value(args, &mut i)?)?),
+            "--set" => presets.push(parse_preset(next_value(args, &mut i)?)?),
+            "-D" | "--define" => options.defines.push(parse_define(next_value(args, &mut i)?)?),
+            "-I" | "--include-path" => options.include_paths.push(PathBuf::from(next_value(args, &mut i)?)),
+            _ if arg.starts_with('-') && arg != "-" => return Err(format!("unknown option `{}`", arg)),
+            _ if input.is_some() => return Err(String::from("`run` takes a single input")),
+            _ => input = Some(parse_input(arg)),
+        }
+        i += 1;
+    }
+
+    if ranges.is_empty() {
+        ranges.push((0, 16));
+    }
+    let input = input.ok_or_else(|| String::from("no input file"))?;
+    Ok(Command::Run(RunArgs { input, options, max_cycles, ranges, presets }))
+}
+
+// `START..END` is exclusive like a Rust range, a lone `ADDRESS` selects one word.
+fn parse_range(value: &str) -> Result<(usize, usize), String> {
+    let (start, end) = match value.find("..") {
+        Some(index) => (&value[..index], &value[index + 2..]),
+        None => (value, ""),
+    };
+    let start = parse_number("--ram", start)?;
+    let end = if end.is_empty() { start + 1 } else { parse_number("--ram", end)? };
+
+    if start >= end || end > RAM_SIZE {
+        return Err(format!("invalid RAM range `{}`", value));
+    }
+    Ok((start, end))
+}
+
+fn parse_preset(value: &str) -> Result<(usize, u16), String> {
+    let index = value.find('=').ok_or_else(|| format!("expected `ADDRESS=VALUE`, found `{}`", value))?;
+    let address = parse_number("--set", &value[..index])?;
+    let word = value[index + 1..].parse::<i32>()
+        .ok()
+        .filter(|word| (-32768..=65535).contains(word))
+        .ok_or_else(|| format!("invalid value `{}` for `--set`", &value[index + 1..]))?;
+
+    if address >= RAM_SIZE {
+        return Err(format!("RAM address {} is out of range", address));
+    }
+    Ok((address, word as u16))
+}
+
+fn parse_test(args: &[String]) -> Result<Command, String> {
+    let mut scripts: Vec<PathBuf> = vec![];
+
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ => scripts.push(PathBuf::from(arg)),
+        }
+    }
+
+    if scripts.is_empty() {
+        return Err(String::from("no test scripts"));
+    }
+    Ok(Command::Test(scripts))
+}
+
+fn parse_translate(args: &[String]) -> Result<Command, String> {
+    let mut input: Option<PathBuf> = None;
+    let mut output: Option<Output> = None;
+    let mut bootstrap: Option<bool> = None;
+    let mut assemble = false;
+    let mut i = 0;
+
+    while i < args.len() {
+        let arg = args[i].as_str();
+        match arg {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" | "--output" => output = Some(parse_output(next_value(args, &mut i)?)),
+            "--bootstrap" => bootstrap = Some(true),
+            "--no-bootstrap" => bootstrap = Some(false),
+            "--hack" => assemble = true,
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ if input.is_some() => return Err(String::from("`vm` takes a single file or directory")),
+            _ => input = Some(PathBuf::from(arg)),
+        }
+        i += 1;
+    }
+
+    let input = input.ok_or_else(|| String::from("no input file or directory"))?;
+    Ok(Command::Translate(TranslateArgs { input, output, bootstrap, assemble }))
+}
+
+fn next_value<'a>(args: &'a [String], i: &mut usize) -> Result<&'a str, String> {
+    let option = &args[*i];
+    *i += 1;
+    args.get(*i).map(|value| value.as_str()).ok_or_else(|| format!("missing value for `{}`", option))
+}
+
+fn parse_number(option: &str, value: &str) -> Result<usize, String> {
+    value.parse().map_err(|_| format!("invalid value `{}` for `{}`", value, option))
+}
+
+fn parse_input(arg: &str) -> Input {
+    if arg == "-" { Input::Stdin } else { Input::File(PathBuf::from(arg)) }
+}
+
+fn parse_output(arg: &str) -> Output {
+    if arg == "-" { Output::Stdout } else { Output::File(PathBuf::from(arg)) }
+}
@@ -0,0 +1,435 @@
+pub mod args;
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use crate::cli::args::{Command, AssembleArgs, DisassembleArgs, LinkArgs, RunArgs, TranslateArgs, Input, Output};
+use crate::disassembler::{disassemble, read_words};
+use crate::emulator::{Cpu, RunResult};
+use crate::linker::link;
+use crate::linker::object::Object;
+use crate::parser::compiler::{self, compile, CompileOptions};
+use crate::output::listing::listing;
+use crate::output::source_map::source_map;
+use crate::output::symbols::{sym_file, symbols_json};
+use crate::parser::error::Diagnostic;
+use crate::tester::{compare, run_script, Files};
+use crate::tester::script::parse_script;
+use crate::vm::{translate, TranslateOptions, VmFile};
+
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+Usage: assembler_rust [-o <output>] [-f <format>] [--rom-size <n>] [--max-errors <n>]
+                      [--dialect <dialect>] [-D <name>[=<value>]]... [-I <dir>]...
+                      [--listing] [--symbols] [--symbols-json] [--source-map] [--object]
+                      <input>...
+       assembler_rust disasm [-o <output>] [--no-labels] [--no-symbols] [--dest-style <style>] <input>
+       assembler_rust run [--cycles <n>] [--set <addr>=<value>]... [--ram <range>]... [-D <name>[=<value>]]... [-I <dir>]... <input>
+       assembler_rust test <script.tst>...
+       assembler_rust vm [-o <output>] [--bootstrap | --no-bootstrap] [--hack] <file.vm | dir>
+       assembler_rust link [-o <output>] [-f <format>] [--rom-size <n>] <object.hobj>...
+
+Assembles Hack `.asm` files. Each input is written to a sibling `.hack` file,
+or a file with the extension of the chosen format, unless `-o` is given. Use `-` to read from standard input or, as the value
+of `-o`, to write to standard output.
+
+`disasm` turns a `.hack` file back into assembly, written to standard output
+unless `-o` is given.
+
+`run` executes a `.asm` or `.hack` program on the built-in Hack CPU until it
+halts or the cycle limit is reached, then prints the selected RAM ranges.
+
+`test` runs nand2tetris `.tst` scripts against the emulator, writes their
+`.out` files and compares them with the `.cmp` file named by `compare-to`.
+
+`vm` translates a `.vm` file, or every `.vm` file of a directory, to Hack
+assembly written next to the input (`dir/dir.asm` for directories). The
+bootstrap code is emitted for directories unless `--no-bootstrap` is given;
+`--hack` assembles the translation and writes a `.hack` file instead.
+
+`link` lays out modules assembled with `--object` in ROM in the order given,
+resolves their `.extern` labels against the `.export`s of the others and
+writes the program next to the first object unless `-o` is given.
+
+Options:
+  -o, --output <path>  write the result to <path> (single input only)
+  -f, --format <name>  output format: hack, hex, bin (big-endian), bin-le,
+                       ihex (Intel HEX), logisim (v2.0 raw image), readmemb,
+                       readmemh, vhdl or verilog (ROM module); default hack
+  --rom-size <n>       pad memory files and ROM modules to <n> words (default 32768)
+  --max-errors <n>     stop reporting after <n> errors (default 20)
+  --dialect <dialect>  `strict` (default) warns about extensions to the Hack
+                       language, `permissive` accepts them silently
+  -D, --define <name>[=<value>]
+                       define <name> (as 1 without a value) for `.if`,
+                       `.ifdef` and `.ifndef`
+  -I, --include-path <dir>
+                       search <dir> for `.include`d files not found next to
+                       the including file
+  --listing            also write a `.lst` listing next to the output
+  --symbols            also write the symbol table as a `.sym` file
+  --symbols-json       also write the symbol table as a `.sym.json` file
+  --source-map         also write a `.map.json` from ROM addresses to source lines
+  --object             write a relocatable `.hobj` module for `link` instead of
+                       the program
+  --no-labels          disasm: keep jump targets as plain addresses
+  --no-symbols         disasm: do not use predefined symbols
+  --dest-style <style> disasm: spell destinations `classic` (MD, AMD, default)
+                       or `modern` (DM, ADM)
+  --cycles <n>         run: stop after <n> cycles (default 1000000)
+  --set <addr>=<value> run: store <value> in RAM[<addr>] before starting
+  --ram <range>        run: print RAM[<start>..<end>] or RAM[<addr>] (default 0..16)
+  -h, --help           print this message
+";
+
+pub struct Console<'a> {
+    pub stdin: &'a mut dyn Read,
+    pub stdout: &'a mut dyn Write,
+    pub stderr: &'a mut dyn Write,
+}
+
+pub fn run(args: &[String], console: &mut Console) -> i32 {
+    match args::parse(args) {
+        Ok(Command::Help) => {
+            let _ = console.stdout.write_all(USAGE.as_bytes());
+            EXIT_SUCCESS
+        },
+        Ok(Command::Assemble(args)) => assemble(args, console),
+        Ok(Command::Disassemble(args)) => report(disassemble_one(&args, console), console),
+        Ok(Command::Run(args)) => report(run_program(&args, console), console),
+        Ok(Command::Translate(args)) => report(translate_vm(&args, console), console),
+        Ok(Command::Link(args)) => report(link_objects(&args, console), console),
+        Ok(Command::Test(scripts)) => {
+            let results: Vec<i32> = scripts.iter()
+                .map(|script| report(run_test(script, console), console))
+                .collect();
+            results.into_iter().max().unwrap_or(EXIT_SUCCESS)
+        },
+        Err(message) => {
+            let _ = writeln!(console.stderr, "error: {}\n\n{}", message, USAGE);
+            EXIT_USAGE
+        }
+    }
+}
+
+fn report(result: Result<(), String>, console: &mut Console) -> i32 {
+    match result {
+        Ok(()) => EXIT_SUCCESS,
+        Err(message) => {
+            let _ = writeln!(console.stderr, "error: {}", message);
+            EXIT_FAILURE
+        }
+    }
+}
+
+fn assemble(args: AssembleArgs, console: &mut Console) -> i32 {
+    let mut status = EXIT_SUCCESS;
+
+    for input in &args.inputs {
+        if report(assemble_one(input, &args, console), console) != EXIT_SUCCESS {
+            status = EXIT_FAILURE;
+        }
+    }
+
+    status
+}
+
+fn assemble_one(input: &Input, args: &AssembleArgs, console: &mut Console) -> Result<(), String> {
+    let source = read_input(input, console)?;
+    let options = &input_options(input, &args.options);
+
+    let assembly = match compiler::assemble(source.clone(), options) {
+        Ok(assembly) => assembly,
+        Err(diagnostics) => {
+            print_diagnostics(&diagnostics, input, &source, console);
+            if diagnostics.len() >= options.max_errors {
+                let _ = writeln!(console.stderr, "note: stopped after {} errors, see `--max-errors`", diagnostics.len());
+            }
+            return Err(format!("could not assemble `{}` due to {} previous error(s)", input.name(), diagnostics.len()));
+        }
+    };
+    print_diagnostics(&assembly.warnings, input, &source, console);
+
+    let destination = match (&args.output, input) {
+        (Some(Output::Stdout), _) | (None, Input::Stdin) => None,
+        (Some(Output::File(path)), _) => Some(path.clone()),
+        (None, Input::File(path)) if args.object => Some(path.with_extension("hobj")),
+        (None, Input::File(path)) => Some(path.with_extension(args.format.extension())),
+    };
+
+    if args.listing {
+        let path = sidecar(&destination, input, "lst")?;
+        write_output(Some(path), listing(&assembly), console)?;
+    }
+    if args.symbols {
+        let path = sidecar(&destination, input, "sym")?;
+        write_output(Some(path), sym_file(&assembly), console)?;
+    }
+    if args.symbols_json {
+        let path = sidecar(&destination, input, "sym.json")?;
+        write_output(Some(path), symbols_json(&assembly), console)?;
+    }
+    if args.source_map {
+        let path = sidecar(&destination, input, "map.json")?;
+        write_output(Some(path), source_map(&assembly, &input.name()), console)?;
+    }
+    if args.object {
+        let name = match input {
+            Input::File(path) => path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default(),
+            Input::Stdin => String::from("stdin"),
+        };
+        return write_output(destination, Object::from_assembly(&name, &assembly).write(), console);
+    }
+    let words = assembly.machine_words();
+    if words.len() > args.format_options.rom_size {
+        return Err(format!("`{}` needs {} words, the ROM holds {}", input.name(), words.len(), args.format_options.rom_size));
+    }
+    write_output(destination, args.format.write(&words, &args.format_options), console)
+}
+
+fn link_objects(args: &LinkArgs, console: &mut Console) -> Result<(), String> {
+    let mut objects = vec![];
+    for path in &args.inputs {
+        let text = read_input(&Input::File(path.clone()), console)?;
+        objects.push(Object::parse(&text).map_err(|e| format!("cannot load `{}`: {}", path.display(), e))?);
+    }
+
+    let words = link(&objects).map_err(|errors| {
+        for error in &errors {
+            let _ = writeln!(console.stderr, "error: {}", error);
+        }
+        format!("could not link due to {} previous error(s)", errors.len())
+    })?;
+    if words.len() > args.format_options.rom_size {
+        return Err(format!("the linked program needs {} words, the ROM holds {}", words.len(), args.format_options.rom_size));
+    }
+
+    let destination = match &args.output {
+        Some(Output::Stdout) => None,
+        Some(Output::File(path)) => Some(path.clone()),
+        None => Some(args.inputs[0].with_extension(args.format.extension())),
+    };
+    write_output(destination, args.format.write(&words, &args.format_options), console)
+}
+
+// Extra outputs are written next to the `.hack` file, or next to the input
+// when the program goes to standard output.
+fn sidecar(destination: &Option<PathBuf>, input: &Input, extension: &str) -> Result<PathBuf, String> {
+    match (destination, input) {
+        (Some(path), _) | (None, Input::File(path)) => Ok(path.with_extension(extension)),
+        (None, Input::Stdin) => Err(format!("cannot place the `.{}` file of standard input, use `-o`", extension)),
+    }
+}
+
+fn disassemble_one(args: &DisassembleArgs, console: &mut Console) -> Result<(), String> {
+    let hack = read_input(&args.input, console)?;
+
+    let asm = match disassemble(&hack, &args.options) {
+        Ok(asm) => asm,
+        Err(diagnostics) => {
+            print_diagnostics(&diagnostics, &args.input, &hack, console);
+            return Err(format!("could not disassemble `{}`", args.input.name()));
+        }
+    };
+
+    let destination = match &args.output {
+        Some(Output::File(path)) => Some(path.clone()),
+        Some(Output::Stdout) | None => None,
+    };
+
+    write_output(destination, &asm, console)
+}
+
+fn run_program(args: &RunArgs, console: &mut Console) -> Result<(), String> {
+    let program = load_program(&args.input, &args.options, console)?;
+    let mut cpu = Cpu::new();
+    cpu.load(&program)?;
+    for (address, value) in &args.presets {
+        cpu.ram_mut()[*address] = *value;
+    }
+
+    let state = match cpu.run_until_halt(args.max_cycles) {
+        RunResult::Halted => "halted",
+        RunResult::CycleLimit => "stopped at the cycle limit",
+    };
+    let mut report = format!("{} after {} cycles\n", state, cpu.cycles());
+    for (start, end) in &args.ranges {
+        for address in *start..*end {
+            report.push_str(&format!("RAM[{}] = {}\n", address, cpu.ram()[address] as i16));
+        }
+    }
+
+    write_output(None, &report, console)
+}
+
+// `.include`s in a file resolve next to it.
+fn input_options(input: &Input, options: &CompileOptions) -> CompileOptions {
+    CompileOptions {
+        path: match input {
+            Input::File(path) => Some(path.clone()),
+            Input::Stdin => None,
+        },
+        ..options.clone()
+    }
+}
+
+// `.hack` inputs are loaded as they are, anything else is assembled first.
+fn load_program(input: &Input, options: &CompileOptions, console: &mut Console) -> Result<Vec<u16>, String> {
+    let source = read_input(input, console)?;
+    let is_hack = matches!(input, Input::File(path) if path.extension().is_some_and(|ext| ext == "hack"));
+    let hack = if is_hack {
+        source.clone()
+    } else {
+        compiler::compile_with_options(source.clone(), &input_options(input, options)).map_err(|diagnostics| {
+            print_diagnostics(&diagnostics, input, &source, console);
+            format!("could not assemble `{}`", input.name())
+        })?
+    };
+
+    read_words(&hack).map_err(|diagnostics| {
+        print_diagnostics(&diagnostics, input, &hack, console);
+        format!("could not load `{}`", input.name())
+    })
+}
+
+struct ScriptFiles {
+    dir: PathBuf,
+}
+
+impl Files for ScriptFiles {
+    fn read(&mut self, name: &str) -> Result<String, String> {
+        let path = self.dir.join(name);
+        fs::read_to_string(&path).map_err(|e| format!("cannot read `{}`: {}", path.display(), e))
+    }
+}
+
+fn run_test(script: &Path, console: &mut Console) -> Result<(), String> {
+    let input = Input::File(script.to_path_buf());
+    let text = read_input(&input, console)?;
+    let commands = parse_script(&text).map_err(|diagnostic| {
+        print_diagnostics(&[diagnostic], &input, &text, console);
+        format!("could not parse `{}`", script.display())
+    })?;
+
+    let dir = script.parent().map(Path::to_path_buf).unwrap_or_default();
+    let run = run_script(&commands, &mut ScriptFiles { dir: dir.clone() })
+        .map_err(|message| format!("{}:{}", script.display(), message))?;
+
+    for echo in &run.echoes {
+        let _ = writeln!(console.stdout, "{}", echo);
+    }
+    let out = match &run.output_file {
+        Some(name) => dir.join(name),
+        None => script.with_extension("out"),
+    };
+    write_output(Some(out), &run.output, console)?;
+
+    match &run.compare_to {
+        Some(expected) => match compare(&run.output, expected) {
+            Ok(()) => {
+                let _ = writeln!(console.stdout, "{}: comparison ended successfully", script.display());
+                Ok(())
+            },
+            Err(mismatch) => Err(format!(
+                "{}: comparison failure at line {}\n  expected: {}\n  actual:   {}",
+                script.display(), mismatch.line, mismatch.expected, mismatch.actual
+            )),
+        },
+        None => {
+            let _ = writeln!(console.stdout, "{}: end of script", script.display());
+            Ok(())
+        }
+    }
+}
+
+fn translate_vm(args: &TranslateArgs, console: &mut Console) -> Result<(), String> {
+    let is_dir = args.input.is_dir();
+    let paths = if is_dir {
+        let mut paths: Vec<PathBuf> = fs::read_dir(&args.input)
+            .map_err(|e| format!("cannot read `{}`: {}", args.input.display(), e))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "vm"))
+            .collect();
+        paths.sort();
+        paths
+    } else {
+        vec![args.input.clone()]
+    };
+    if paths.is_empty() {
+        return Err(format!("no `.vm` files in `{}`", args.input.display()));
+    }
+
+    let mut files = vec![];
+    for path in &paths {
+        files.push(VmFile {
+            name: path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default(),
+            source: read_input(&Input::File(path.clone()), console)?,
+        });
+    }
+
+    let options = TranslateOptions { bootstrap: args.bootstrap.unwrap_or(is_dir) };
+    let asm = translate(&files, &options).map_err(|errors| {
+        for (index, diagnostic) in &errors {
+            let _ = writeln!(console.stderr, "{}", diagnostic.render(&paths[*index].display().to_string(), &files[*index].source));
+        }
+        format!("could not translate `{}`", args.input.display())
+    })?;
+
+    let (contents, extension) = if args.assemble {
+        let hack = compile(asm.clone()).map_err(|diagnostics| {
+            print_diagnostics(&diagnostics, &Input::File(args.input.with_extension("asm")), &asm, console);
+            format!("could not assemble the translation of `{}`", args.input.display())
+        })?;
+        (format!("{}\n", hack), "hack")
+    } else {
+        (asm, "asm")
+    };
+
+    let destination = match &args.output {
+        Some(Output::Stdout) => None,
+        Some(Output::File(path)) => Some(path.clone()),
+        None if is_dir => {
+            let name = args.input.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            Some(args.input.join(name).with_extension(extension))
+        },
+        None => Some(args.input.with_extension(extension)),
+    };
+
+    write_output(destination, &contents, console)
+}
+
+fn read_input(input: &Input, console: &mut Console) -> Result<String, String> {
+    match input {
+        Input::Stdin => {
+            let mut buffer = String::new();
+            console.stdin.read_to_string(&mut buffer)
+                .map_err(|e| format!("cannot read standard input: {}", e))?;
+            Ok(buffer)
+        },
+        Input::File(path) => fs::read_to_string(path)
+            .map_err(|e| format!("cannot read `{}`: {}", path.display(), e))
+    }
+}
+
+fn write_output(destination: Option<PathBuf>, contents: impl AsRef<[u8]>, console: &mut Console) -> Result<(), String> {
+    match destination {
+        Some(path) => fs::write(&path, contents)
+            .map_err(|e| format!("cannot write `{}`: {}", path.display(), e)),
+        None => console.stdout.write_all(contents.as_ref())
+            .map_err(|e| format!("cannot write standard output: {}", e)),
+    }
+}
+
+// Diagnostics in included files quote their own source.
+fn print_diagnostics(diagnostics: &[Diagnostic], input: &Input, source: &str, console: &mut Console) {
+    for diagnostic in diagnostics {
+        let rendered = match &diagnostic.file {
+            Some(file) => diagnostic.render(file, &fs::read_to_string(file).unwrap_or_default()),
+            None => diagnostic.render(&input.name(), source),
+        };
+        let _ = writeln!(console.stderr, "{}", rendered);
+    }
+}
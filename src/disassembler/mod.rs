@@ -0,0 +1,177 @@
+use std::collections::{HashMap, HashSet};
+use crate::parser::error::{AssembleError, Diagnostic, Field};
+use crate::parser::table::{dest_spelling, DestStyle, HACK_INSTRUCTIONS};
+use crate::parser::parser::PREDEFINED_SYMBOLS;
+use crate::parser::span::Span;
+
+lazy_static! {
+    // Keyed by the `a` bit followed by the six `c` bits.
+    static ref COMP_BY_BITS: HashMap<&'static str, &'static str> = HACK_INSTRUCTIONS.comp.iter()
+        .map(|(mnemonic, bits)| (*bits, *mnemonic))
+        .collect();
+    static ref DEST_BY_BITS: HashMap<&'static str, &'static str> = HACK_INSTRUCTIONS.dest.iter()
+        .map(|(mnemonic, bits)| (*bits, *mnemonic))
+        .collect();
+    static ref JMP_BY_BITS: HashMap<&'static str, &'static str> = HACK_INSTRUCTIONS.jump.iter()
+        .map(|(mnemonic, bits)| (*bits, *mnemonic))
+        .collect();
+    // Later entries override earlier ones, so `R0`..`R4` win over the VM
+    // pointer aliases `SP`..`THAT`.
+    static ref SYMBOL_BY_ADDRESS: HashMap<u32, &'static str> = PREDEFINED_SYMBOLS.iter()
+        .map(|(name, address)| (*address, *name))
+        .collect();
+}
+
+pub struct DisassembleOptions {
+    // Replace the targets of jumps with synthesized `(L_nnn)` labels.
+    pub labels: bool,
+    // Use predefined symbols for addresses that are accessed through `M`.
+    pub symbols: bool,
+    pub dest_style: DestStyle,
+}
+
+impl Default for DisassembleOptions {
+    fn default() -> Self {
+        DisassembleOptions { labels: true, symbols: true, dest_style: DestStyle::Classic }
+    }
+}
+
+pub enum Instruction {
+    A(u16),
+    C { dest: Option<&'static str>, comp: &'static str, jump: Option<&'static str> },
+}
+
+impl Instruction {
+    fn accesses_memory(&self) -> bool {
+        match self {
+            Instruction::A(_) => false,
+            Instruction::C { dest, comp, .. } => comp.contains('M') || dest.is_some_and(|d| d.contains('M')),
+        }
+    }
+}
+
+pub fn read_words(hack: &str) -> Result<Vec<u16>, Vec<Diagnostic>> {
+    Ok(read_spanned_words(hack)?.into_iter().map(|(word, _)| word).collect())
+}
+
+fn read_spanned_words(hack: &str) -> Result<Vec<(u16, Span)>, Vec<Diagnostic>> {
+    let mut words = vec![];
+    let mut diagnostics = vec![];
+
+    for (index, line) in hack.lines().enumerate() {
+        let text = line.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let column = line.find(text).unwrap_or(0) + 1;
+        let span = Span::new(index + 1, column, text.chars().count());
+        if text.len() != 16 || !text.chars().all(|c| c == '0' || c == '1') {
+            let error = AssembleError::Lexical(String::from("expected a 16-bit binary word"));
+            diagnostics.push(Diagnostic::new(error, span));
+            continue;
+        }
+        words.push((u16::from_str_radix(text, 2).unwrap(), span));
+    }
+
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+    Ok(words)
+}
+
+pub fn decode(word: u16) -> Result<Instruction, AssembleError> {
+    if word & 0x8000 == 0 {
+        return Ok(Instruction::A(word));
+    }
+    if word & 0x6000 != 0x6000 {
+        return Err(AssembleError::Syntax(format!("invalid c-instruction `{:016b}`", word)));
+    }
+
+    let bits = format!("{:016b}", word);
+    let comp = COMP_BY_BITS.get(&bits[3..10])
+        .ok_or_else(|| AssembleError::UnknownMnemonic(Field::Comp, String::from(&bits[3..10])))?;
+    let dest = DEST_BY_BITS.get(&bits[10..13]).copied();
+    let jump = JMP_BY_BITS.get(&bits[13..16]).copied();
+
+    Ok(Instruction::C { dest, comp, jump })
+}
+
+pub fn disassemble(hack: &str, options: &DisassembleOptions) -> Result<String, Vec<Diagnostic>> {
+    let words = read_spanned_words(hack)?;
+    let mut instructions = vec![];
+    let mut diagnostics = vec![];
+
+    for (word, span) in words {
+        match decode(word) {
+            Ok(instruction) => instructions.push(instruction),
+            Err(error) => diagnostics.push(Diagnostic::new(error, span)),
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    let targets = if options.labels { jump_targets(&instructions) } else { HashSet::new() };
+    let mut lines: Vec<String> = vec![];
+
+    for (address, instruction) in instructions.iter().enumerate() {
+        if targets.contains(&address) {
+            lines.push(format!("(L_{})", address));
+        }
+
+        lines.push(match instruction {
+            Instruction::A(value) => {
+                let next = instructions.get(address + 1);
+                let symbol = SYMBOL_BY_ADDRESS.get(&(*value as u32))
+                    .filter(|_| options.symbols)
+                    .filter(|name| !name.starts_with('R') || next.is_some_and(|i| i.accesses_memory()));
+
+                if is_jump(next) && targets.contains(&(*value as usize)) {
+                    format!("@L_{}", value)
+                } else if let Some(name) = symbol {
+                    format!("@{}", name)
+                } else {
+                    format!("@{}", value)
+                }
+            },
+            Instruction::C { dest, comp, jump } => {
+                let mut line = String::new();
+                if let Some(dest) = dest {
+                    line.push_str(dest_spelling(dest, options.dest_style));
+                    line.push('=');
+                }
+                line.push_str(comp);
+                if let Some(jump) = jump {
+                    line.push(';');
+                    line.push_str(jump);
+                }
+                line
+            }
+        });
+    }
+
+    if targets.contains(&instructions.len()) {
+        lines.push(format!("(L_{})", instructions.len()));
+    }
+
+    let mut buffer = lines.join("\n");
+    buffer.push('\n');
+    Ok(buffer)
+}
+
+fn is_jump(instruction: Option<&Instruction>) -> bool {
+    matches!(instruction, Some(Instruction::C { jump: Some(_), .. }))
+}
+
+// An `@value` right before a jump loads the jump target; labels can sit on
+// any instruction and right after the last one.
+fn jump_targets(instructions: &[Instruction]) -> HashSet<usize> {
+    instructions.windows(2)
+        .filter_map(|pair| match pair {
+            [Instruction::A(value), next] if is_jump(Some(next)) => Some(*value as usize),
+            _ => None
+        })
+        .filter(|target| *target <= instructions.len())
+        .collect()
+}
@@ -0,0 +1,175 @@
+pub const ROM_SIZE: usize = 32768;
+pub const RAM_SIZE: usize = 32768;
+pub const SCREEN: usize = 16384;
+pub const SCREEN_SIZE: usize = 8192;
+pub const KBD: usize = 24576;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunResult {
+    Halted,
+    CycleLimit,
+}
+
+pub struct Cpu {
+    pub a: u16,
+    pub d: u16,
+    pub pc: u16,
+    rom: Vec<u16>,
+    ram: Vec<u16>,
+    program_len: usize,
+    cycles: u64,
+    halted: bool,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Cpu::new()
+    }
+}
+
+impl Cpu {
+    pub fn new() -> Cpu {
+        Cpu {
+            a: 0,
+            d: 0,
+            pc: 0,
+            rom: vec![0; ROM_SIZE],
+            ram: vec![0; RAM_SIZE],
+            program_len: 0,
+            cycles: 0,
+            halted: false,
+        }
+    }
+
+    pub fn load(&mut self, program: &[u16]) -> Result<(), String> {
+        if program.len() > ROM_SIZE {
+            return Err(format!("program has {} words, the ROM holds {}", program.len(), ROM_SIZE));
+        }
+
+        self.rom.iter_mut().for_each(|word| *word = 0);
+        self.rom[..program.len()].copy_from_slice(program);
+        self.program_len = program.len();
+        self.reset();
+        Ok(())
+    }
+
+    // Restarts the program; like the hardware reset, RAM is left untouched.
+    pub fn reset(&mut self) {
+        self.a = 0;
+        self.d = 0;
+        self.pc = 0;
+        self.cycles = 0;
+        self.halted = false;
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted || self.pc as usize >= self.program_len
+    }
+
+    pub fn rom(&self) -> &[u16] {
+        &self.rom
+    }
+
+    pub fn ram(&self) -> &[u16] {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut [u16] {
+        &mut self.ram
+    }
+
+    pub fn screen(&self) -> &[u16] {
+        &self.ram[SCREEN..SCREEN + SCREEN_SIZE]
+    }
+
+    pub fn set_key(&mut self, key: u16) {
+        self.ram[KBD] = key;
+    }
+
+    pub fn step(&mut self) {
+        let pc = self.pc as usize % ROM_SIZE;
+        let instruction = self.rom[pc];
+        self.cycles += 1;
+        self.halted = false;
+
+        if instruction & 0x8000 == 0 {
+            self.a = instruction;
+            self.pc = self.pc.wrapping_add(1);
+            return;
+        }
+
+        let address = self.a as usize % RAM_SIZE;
+        let uses_m = instruction & 0x1000 != 0;
+        let y = if uses_m { self.ram[address] } else { self.a };
+        let out = alu(self.d, y, (instruction >> 6) & 0x3f);
+
+        if instruction & 0x08 != 0 {
+            self.ram[address] = out;
+        }
+        let jump_target = self.a;
+        if instruction & 0x20 != 0 {
+            self.a = out;
+        }
+        if instruction & 0x10 != 0 {
+            self.d = out;
+        }
+
+        let negative = out & 0x8000 != 0;
+        let zero = out == 0;
+        let jump = (instruction & 0x04 != 0 && negative)
+            || (instruction & 0x02 != 0 && zero)
+            || (instruction & 0x01 != 0 && !negative && !zero);
+
+        if jump {
+            self.halted = self.is_idle_loop(pc, jump_target as usize);
+            self.pc = jump_target;
+        } else {
+            self.pc = self.pc.wrapping_add(1);
+        }
+    }
+
+    pub fn run(&mut self, cycles: u64) -> RunResult {
+        for _ in 0..cycles {
+            if self.is_halted() {
+                return RunResult::Halted;
+            }
+            self.step();
+        }
+
+        if self.is_halted() { RunResult::Halted } else { RunResult::CycleLimit }
+    }
+
+    pub fn run_until_halt(&mut self, max_cycles: u64) -> RunResult {
+        self.run(max_cycles)
+    }
+
+    // A taken jump back onto itself, or onto the `@target` that precedes it,
+    // without writing any register can never leave the loop: the usual
+    // `(END) @END 0;JMP` ending of Hack programs.
+    fn is_idle_loop(&self, pc: usize, target: usize) -> bool {
+        let instruction = self.rom[pc];
+        let writes = instruction & 0x38 != 0;
+        let reads_memory = instruction & 0x1000 != 0;
+        if writes || reads_memory {
+            return false;
+        }
+
+        target == pc || (target + 1 == pc && self.rom[target] as usize == target)
+    }
+}
+
+// The Hack ALU driven by the six control bits `zx nx zy ny f no`.
+fn alu(x: u16, y: u16, control: u16) -> u16 {
+    let mut x = x;
+    let mut y = y;
+    if control & 0x20 != 0 { x = 0; }
+    if control & 0x10 != 0 { x = !x; }
+    if control & 0x08 != 0 { y = 0; }
+    if control & 0x04 != 0 { y = !y; }
+    let out = if control & 0x02 != 0 { x.wrapping_add(y) } else { x & y };
+    if control & 0x01 != 0 { !out } else { out }
+}
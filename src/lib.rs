@@ -1,5 +1,13 @@
 #[macro_use]
 extern crate lazy_static;
 pub mod parser;
+pub mod preprocessor;
+pub mod disassembler;
+pub mod emulator;
+pub mod tester;
+pub mod vm;
+pub mod linker;
+pub mod output;
+pub mod cli;
 #[cfg(test)]
 pub mod tests;
\ No newline at end of file
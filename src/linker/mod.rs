@@ -0,0 +1,103 @@
+pub mod object;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use crate::linker::object::{Object, Target};
+use crate::parser::parser::SymTable;
+use crate::parser::tokenizer::MAX_A_CONSTANT;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkError {
+    // Label, the module exporting it first and the module exporting it again.
+    DuplicateExport(String, String, String),
+    // Label and the module importing it.
+    UnresolvedImport(String, String),
+    // Module, what the address was resolved for and the address, past the
+    // ones an A-instruction can load.
+    AddressOutOfRange(String, Target, usize),
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LinkError::DuplicateExport(name, first, second) =>
+                write!(f, "`{}` is exported by both `{}` and `{}`", name, first, second),
+            LinkError::UnresolvedImport(name, module) =>
+                write!(f, "`{}` imports `{}`, which no module exports", module, name),
+            LinkError::AddressOutOfRange(module, target, address) => {
+                let location = match target {
+                    Target::Label => format!("ROM address {}", address),
+                    Target::Import(name) => format!("`{}` at ROM address {}", name, address),
+                    Target::Variable(name) => format!("variable `{}` at RAM address {}", name, address),
+                };
+                write!(f, "`{}` refers to {}, past the {} an A-instruction can load", module, location, MAX_A_CONSTANT)
+            },
+        }
+    }
+}
+
+impl Error for LinkError {}
+
+// Lays the modules out in ROM in the given order, resolves imports against
+// the exports of every module and allocates variables from RAM address 16,
+// shared by name across modules as within a single program.
+pub fn link(objects: &[Object]) -> Result<Vec<u16>, Vec<LinkError>> {
+    let mut errors = vec![];
+    let mut bases = vec![];
+    let mut size = 0;
+    for object in objects {
+        bases.push(size);
+        size += object.code.len();
+    }
+
+    let mut exports: HashMap<&str, (usize, &str)> = HashMap::new();
+    for (object, base) in objects.iter().zip(&bases) {
+        for (name, address) in &object.exports {
+            match exports.get(name.as_str()) {
+                Some((_, first)) => errors.push(LinkError::DuplicateExport(name.clone(), String::from(*first), object.name.clone())),
+                None => { exports.insert(name, (base + *address as usize, &object.name)); },
+            }
+        }
+    }
+
+    for object in objects {
+        for name in object.imports.iter().filter(|name| !exports.contains_key(name.as_str())) {
+            errors.push(LinkError::UnresolvedImport(name.clone(), object.name.clone()));
+        }
+    }
+
+    let mut variables = SymTable::new();
+    let mut words = vec![];
+    for (object, base) in objects.iter().zip(&bases) {
+        let mut code = object.code.clone();
+        for relocation in &object.relocations {
+            let address = match &relocation.target {
+                Target::Label => base + code[relocation.address] as usize,
+                Target::Import(name) => match exports.get(name.as_str()) {
+                    Some((address, _)) => *address,
+                    // Reported with the imports above.
+                    None => continue,
+                },
+                Target::Variable(name) => {
+                    if variables.get(name.clone()).is_none() {
+                        variables.add(name.clone());
+                    }
+                    variables.get(name.clone()).copied().unwrap_or(0) as usize
+                },
+            };
+
+            if address > MAX_A_CONSTANT as usize {
+                errors.push(LinkError::AddressOutOfRange(object.name.clone(), relocation.target.clone(), address));
+                continue;
+            }
+            code[relocation.address] = address as u16;
+        }
+        words.extend(code);
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(words)
+}
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use crate::parser::compiler::Assembly;
+use crate::parser::parser::{Symbol, SymbolKind};
+use crate::parser::tokenizer::MAX_A_CONSTANT;
+
+pub const MAGIC: &str = "hack-object 1";
+
+// A separately assembled module. Its code is assembled as if it started at
+// ROM address 0; the relocations list the A-instructions the linker patches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object {
+    pub name: String,
+    pub code: Vec<u16>,
+    // Labels other modules may refer to, with their module-relative address.
+    pub exports: Vec<(String, u16)>,
+    pub imports: Vec<String>,
+    pub relocations: Vec<Relocation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    pub address: usize,
+    pub target: Target,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    // A label of the module; the word holds its module-relative address.
+    Label,
+    // A label exported by another module.
+    Import(String),
+    // A variable, allocated in RAM across all modules.
+    Variable(String),
+}
+
+impl Object {
+    pub fn from_assembly(name: &str, assembly: &Assembly) -> Object {
+        let symbols: HashMap<&str, &Symbol> = assembly.symbols.iter().map(|symbol| (symbol.name.as_str(), symbol)).collect();
+        let address = |name: &str| symbols.get(name).map_or(0, |symbol| symbol.address as u16);
+
+        let relocations = assembly.references.iter()
+            .filter_map(|reference| {
+                let target = match symbols.get(reference.symbol.as_str())?.kind {
+                    SymbolKind::Label => Target::Label,
+                    SymbolKind::Extern => Target::Import(reference.symbol.clone()),
+                    SymbolKind::Variable => Target::Variable(reference.symbol.clone()),
+                    SymbolKind::Predefined | SymbolKind::Constant => return None,
+                };
+                Some(Relocation { address: reference.address, target })
+            })
+            .collect();
+
+        Object {
+            name: String::from(name),
+            code: assembly.machine_words(),
+            exports: assembly.exports.iter().map(|export| (export.clone(), address(export))).collect(),
+            imports: assembly.symbols.iter()
+                .filter(|symbol| symbol.kind == SymbolKind::Extern)
+                .map(|symbol| symbol.name.clone())
+                .collect(),
+            relocations,
+        }
+    }
+
+    // A line based text format: a header, then `module`, `export`, `import`,
+    // `code` (followed by one hex word per line) and `reloc` records.
+    pub fn write(&self) -> String {
+        let mut buffer = format!("{}\nmodule {}\n", MAGIC, self.name);
+        for (name, address) in &self.exports {
+            buffer.push_str(&format!("export {} {}\n", name, address));
+        }
+        for name in &self.imports {
+            buffer.push_str(&format!("import {}\n", name));
+        }
+        buffer.push_str(&format!("code {}\n", self.code.len()));
+        for word in &self.code {
+            buffer.push_str(&format!("{:04X}\n", word));
+        }
+        for relocation in &self.relocations {
+            let target = match &relocation.target {
+                Target::Label => String::from("label"),
+                Target::Import(name) => format!("import {}", name),
+                Target::Variable(name) => format!("variable {}", name),
+            };
+            buffer.push_str(&format!("reloc {} {}\n", relocation.address, target));
+        }
+        buffer
+    }
+
+    pub fn parse(text: &str) -> Result<Object, String> {
+        let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line.trim()));
+        if lines.next().map(|(_, line)| line) != Some(MAGIC) {
+            return Err(format!("not a Hack object file, expected `{}`", MAGIC));
+        }
+
+        let mut object = Object { name: String::new(), code: vec![], exports: vec![], imports: vec![], relocations: vec![] };
+        while let Some((line, text)) = lines.next() {
+            let fields: Vec<&str> = text.split_whitespace().collect();
+            let invalid = || format!("line {}: invalid record `{}`", line, text);
+            let number = |field: Option<&&str>| field.and_then(|field| field.parse::<usize>().ok()).ok_or_else(invalid);
+
+            match fields.as_slice() {
+                [] => {},
+                ["module", name] => object.name = String::from(*name),
+                ["export", name, _] => {
+                    let address = number(fields.get(2))?;
+                    if address > MAX_A_CONSTANT as usize {
+                        return Err(format!("line {}: export address {} is past the {} an A-instruction can load", line, address, MAX_A_CONSTANT));
+                    }
+                    object.exports.push((String::from(*name), address as u16));
+                },
+                ["import", name] => object.imports.push(String::from(*name)),
+                ["code", _] => {
+                    for _ in 0..number(fields.get(1))? {
+                        let (line, word) = lines.next().ok_or_else(|| format!("line {}: missing code words", line))?;
+                        let word = u16::from_str_radix(word, 16).map_err(|_| format!("line {}: invalid word `{}`", line, word))?;
+                        object.code.push(word);
+                    }
+                },
+                ["reloc", _, "label"] => object.relocations.push(Relocation { address: number(fields.get(1))?, target: Target::Label }),
+                ["reloc", _, "import", name] => object.relocations.push(Relocation {
+                    address: number(fields.get(1))?,
+                    target: Target::Import(String::from(*name)),
+                }),
+                ["reloc", _, "variable", name] => object.relocations.push(Relocation {
+                    address: number(fields.get(1))?,
+                    target: Target::Variable(String::from(*name)),
+                }),
+                _ => return Err(invalid()),
+            }
+        }
+
+        if let Some(relocation) = object.relocations.iter().find(|relocation| relocation.address >= object.code.len()) {
+            return Err(format!("relocation at {} is outside the code", relocation.address));
+        }
+        for relocation in &object.relocations {
+            if let Target::Import(name) = &relocation.target {
+                if !object.imports.contains(name) {
+                    return Err(format!("relocation at {} refers to `{}`, which is not imported", relocation.address, name));
+                }
+            }
+        }
+        Ok(object)
+    }
+}
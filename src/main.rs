@@ -1,8 +1,18 @@
-#![feature(with_options)]
-
+use std::env;
 use std::io;
+use std::process;
+use lib::cli::{self, Console};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let stderr = io::stderr();
+    let mut console = Console {
+        stdin: &mut stdin.lock(),
+        stdout: &mut stdout.lock(),
+        stderr: &mut stderr.lock(),
+    };
 
-fn main() -> io::Result<()> {
-    // TODO: handle asm via cli args
-    Ok(())
+    process::exit(cli::run(&args, &mut console));
 }
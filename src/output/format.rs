@@ -0,0 +1,227 @@
+use crate::emulator::ROM_SIZE;
+
+// Writers turning the assembled words into the files the various Hack
+// machines load: the course `.hack` text, raw images for FPGA builds and
+// ROM images for Logisim.
+pub trait Format {
+    // Name used by `--format`.
+    fn name(&self) -> &'static str;
+    fn extension(&self) -> &'static str;
+    fn write(&self, words: &[u16], options: &FormatOptions) -> Vec<u8>;
+}
+
+pub struct FormatOptions {
+    // Number of ROM words the memory files and HDL modules are padded to.
+    pub rom_size: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions { rom_size: ROM_SIZE }
+    }
+}
+
+pub fn formats() -> Vec<Box<dyn Format>> {
+    vec![
+        Box::new(HackText),
+        Box::new(RawBinary { big_endian: true }),
+        Box::new(RawBinary { big_endian: false }),
+        Box::new(HexText),
+        Box::new(IntelHex),
+        Box::new(LogisimImage),
+        Box::new(MemoryFile { hex: false }),
+        Box::new(MemoryFile { hex: true }),
+        Box::new(VhdlRom),
+        Box::new(VerilogRom),
+    ]
+}
+
+pub fn find_format(name: &str) -> Option<Box<dyn Format>> {
+    formats().into_iter().find(|format| format.name() == name)
+}
+
+pub struct HackText;
+
+impl Format for HackText {
+    fn name(&self) -> &'static str { "hack" }
+    fn extension(&self) -> &'static str { "hack" }
+
+    fn write(&self, words: &[u16], _options: &FormatOptions) -> Vec<u8> {
+        lines(words.iter().map(|word| format!("{:016b}", word)))
+    }
+}
+
+pub struct RawBinary {
+    pub big_endian: bool,
+}
+
+impl Format for RawBinary {
+    fn name(&self) -> &'static str {
+        if self.big_endian { "bin" } else { "bin-le" }
+    }
+
+    fn extension(&self) -> &'static str { "bin" }
+
+    fn write(&self, words: &[u16], _options: &FormatOptions) -> Vec<u8> {
+        words.iter()
+            .flat_map(|word| if self.big_endian { word.to_be_bytes() } else { word.to_le_bytes() })
+            .collect()
+    }
+}
+
+pub struct HexText;
+
+impl Format for HexText {
+    fn name(&self) -> &'static str { "hex" }
+    fn extension(&self) -> &'static str { "hex" }
+
+    fn write(&self, words: &[u16], _options: &FormatOptions) -> Vec<u8> {
+        lines(words.iter().map(|word| format!("{:04X}", word)))
+    }
+}
+
+// Words are stored big-endian at byte address `2 * ROM address`, sixteen
+// bytes per data record.
+pub struct IntelHex;
+
+impl Format for IntelHex {
+    fn name(&self) -> &'static str { "ihex" }
+    fn extension(&self) -> &'static str { "ihx" }
+
+    fn write(&self, words: &[u16], _options: &FormatOptions) -> Vec<u8> {
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_be_bytes()).collect();
+        let mut records: Vec<String> = bytes.chunks(16)
+            .enumerate()
+            .map(|(index, chunk)| intel_hex_record((index * 16) as u16, 0x00, chunk))
+            .collect();
+        records.push(intel_hex_record(0, 0x01, &[]));
+
+        lines(records.into_iter())
+    }
+}
+
+fn intel_hex_record(address: u16, kind: u8, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend_from_slice(&address.to_be_bytes());
+    bytes.push(kind);
+    bytes.extend_from_slice(data);
+    let checksum = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)).wrapping_neg();
+    bytes.push(checksum);
+
+    let hex: String = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+    format!(":{}", hex)
+}
+
+// Logisim-evolution memory image: lowercase hex words, eight per line, runs
+// of four or more equal words written as `count*word`.
+pub struct LogisimImage;
+
+impl Format for LogisimImage {
+    fn name(&self) -> &'static str { "logisim" }
+    fn extension(&self) -> &'static str { "rom" }
+
+    fn write(&self, words: &[u16], _options: &FormatOptions) -> Vec<u8> {
+        let mut entries = vec![];
+        let mut i = 0;
+        while i < words.len() {
+            let run = words[i..].iter().take_while(|word| **word == words[i]).count();
+            if run >= 4 {
+                entries.push(format!("{}*{:x}", run, words[i]));
+                i += run;
+            } else {
+                entries.push(format!("{:x}", words[i]));
+                i += 1;
+            }
+        }
+
+        let mut buffer = String::from("v2.0 raw\n");
+        for row in entries.chunks(8) {
+            buffer.push_str(&row.join(" "));
+            buffer.push('\n');
+        }
+        buffer.into_bytes()
+    }
+}
+
+// Memory files for Verilog `$readmemb` / `$readmemh`, one word per line,
+// zero-filled up to the ROM size.
+pub struct MemoryFile {
+    pub hex: bool,
+}
+
+impl Format for MemoryFile {
+    fn name(&self) -> &'static str {
+        if self.hex { "readmemh" } else { "readmemb" }
+    }
+
+    fn extension(&self) -> &'static str { "mem" }
+
+    fn write(&self, words: &[u16], options: &FormatOptions) -> Vec<u8> {
+        lines(padded(words, options).map(|word| {
+            if self.hex { format!("{:04x}", word) } else { format!("{:016b}", word) }
+        }))
+    }
+}
+
+// A `hack_rom` entity with the program as a constant array; addresses past
+// the program read as zero.
+pub struct VhdlRom;
+
+impl Format for VhdlRom {
+    fn name(&self) -> &'static str { "vhdl" }
+    fn extension(&self) -> &'static str { "vhd" }
+
+    fn write(&self, words: &[u16], options: &FormatOptions) -> Vec<u8> {
+        let bits = address_bits(options.rom_size);
+        let mut buffer = String::new();
+        buffer.push_str("library ieee;\nuse ieee.std_logic_1164.all;\nuse ieee.numeric_std.all;\n\n");
+        buffer.push_str("entity hack_rom is\n    port (\n");
+        buffer.push_str(&format!("        address : in  std_logic_vector({} downto 0);\n", bits - 1));
+        buffer.push_str("        data    : out std_logic_vector(15 downto 0)\n    );\nend entity;\n\n");
+        buffer.push_str("architecture rtl of hack_rom is\n");
+        buffer.push_str(&format!("    type rom_t is array (0 to {}) of std_logic_vector(15 downto 0);\n", options.rom_size - 1));
+        buffer.push_str("    constant ROM : rom_t := (\n");
+        for (address, word) in words.iter().enumerate() {
+            buffer.push_str(&format!("        {} => x\"{:04X}\",\n", address, word));
+        }
+        buffer.push_str("        others => x\"0000\"\n    );\nbegin\n");
+        buffer.push_str("    data <= ROM(to_integer(unsigned(address)));\nend architecture;\n");
+        buffer.into_bytes()
+    }
+}
+
+// A combinational `hack_rom` module with one case arm per word.
+pub struct VerilogRom;
+
+impl Format for VerilogRom {
+    fn name(&self) -> &'static str { "verilog" }
+    fn extension(&self) -> &'static str { "v" }
+
+    fn write(&self, words: &[u16], options: &FormatOptions) -> Vec<u8> {
+        let bits = address_bits(options.rom_size);
+        let mut buffer = String::new();
+        buffer.push_str("module hack_rom (\n");
+        buffer.push_str(&format!("    input  wire [{}:0] address,\n", bits - 1));
+        buffer.push_str("    output reg  [15:0] data\n);\n\n");
+        buffer.push_str("    always @(*) begin\n        case (address)\n");
+        for (address, word) in words.iter().enumerate() {
+            buffer.push_str(&format!("            {}'d{}: data = 16'h{:04X};\n", bits, address, word));
+        }
+        buffer.push_str("            default: data = 16'h0000;\n        endcase\n    end\nendmodule\n");
+        buffer.into_bytes()
+    }
+}
+
+fn padded<'a>(words: &'a [u16], options: &FormatOptions) -> impl Iterator<Item = u16> + 'a {
+    let padding = options.rom_size.saturating_sub(words.len());
+    words.iter().copied().chain(std::iter::repeat_n(0, padding))
+}
+
+fn address_bits(rom_size: usize) -> usize {
+    let bits = (usize::BITS - rom_size.saturating_sub(1).leading_zeros()) as usize;
+    bits.max(1)
+}
+
+fn lines(rows: impl Iterator<Item = String>) -> Vec<u8> {
+    rows.map(|row| format!("{}\n", row)).collect::<String>().into_bytes()
+}
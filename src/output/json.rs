@@ -0,0 +1,17 @@
+// Just enough JSON for the outputs of this crate: a quoted, escaped string.
+pub fn string(text: &str) -> String {
+    let mut buffer = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => buffer.push_str("\\\""),
+            '\\' => buffer.push_str("\\\\"),
+            '\n' => buffer.push_str("\\n"),
+            '\r' => buffer.push_str("\\r"),
+            '\t' => buffer.push_str("\\t"),
+            c if (c as u32) < 0x20 => buffer.push_str(&format!("\\u{:04x}", c as u32)),
+            c => buffer.push(c),
+        }
+    }
+    buffer.push('"');
+    buffer
+}
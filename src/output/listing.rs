@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use crate::parser::compiler::Assembly;
+
+// Every source line is listed next to the words assembled from it; label
+// definitions show the ROM address they name, comments and blank lines are
+// kept so the listing reads like the source. Included files are listed
+// after their `.include` line.
+pub fn listing(assembly: &Assembly) -> String {
+    let mut words_by_line: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (address, span) in assembly.spans.iter().enumerate() {
+        words_by_line.entry((assembly.word_files[address], span.line)).or_default().push(address);
+    }
+    let labels_by_line: HashMap<(usize, usize), u32> = assembly.labels.iter()
+        .map(|label| ((label.file, label.span.line), label.address))
+        .collect();
+
+    let listing = Listing { assembly, words_by_line, labels_by_line, machine_words: assembly.machine_words() };
+    let mut buffer = format!("{:>5}  {:4}  {:16}  {:>5}  {}\n", "ROM", "HEX", "BINARY", "LINE", "SOURCE");
+    listing.file(0, &assembly.source, &mut buffer);
+
+    buffer.lines().map(|row| format!("{}\n", row.trim_end())).collect()
+}
+
+struct Listing<'a> {
+    assembly: &'a Assembly,
+    words_by_line: HashMap<(usize, usize), Vec<usize>>,
+    labels_by_line: HashMap<(usize, usize), u32>,
+    machine_words: Vec<u16>,
+}
+
+impl<'a> Listing<'a> {
+    fn file(&self, file: usize, source: &str, buffer: &mut String) {
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let text = text.trim_end();
+
+            match (self.words_by_line.get(&(file, line)), self.labels_by_line.get(&(file, line))) {
+                (Some(addresses), _) => {
+                    for (i, address) in addresses.iter().enumerate() {
+                        let word = &self.assembly.words[*address];
+                        let hex = self.machine_words[*address];
+                        let (line, text) = if i == 0 { (line.to_string(), text) } else { (String::new(), "") };
+                        buffer.push_str(&format!("{:>5}  {:04X}  {}  {:>5}  {}", address, hex, word, line, text));
+                        buffer.push('\n');
+                    }
+                },
+                (None, Some(address)) => {
+                    buffer.push_str(&format!("{:>5}  {:4}  {:16}  {:>5}  {}", address, "", "", line, text));
+                    buffer.push('\n');
+                },
+                (None, None) => {
+                    buffer.push_str(&format!("{:5}  {:4}  {:16}  {:>5}  {}", "", "", "", line, text));
+                    buffer.push('\n');
+                },
+            }
+
+            for (included, source_file) in self.assembly.files.iter().enumerate() {
+                if source_file.included_from == Some((file, line)) {
+                    self.file(included, &source_file.source, buffer);
+                }
+            }
+        }
+    }
+}
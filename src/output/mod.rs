@@ -0,0 +1,5 @@
+pub mod listing;
+pub mod symbols;
+pub mod source_map;
+pub mod format;
+pub mod json;
@@ -0,0 +1,34 @@
+use crate::output::json;
+use crate::parser::compiler::Assembly;
+use crate::parser::span::Span;
+
+// Maps every ROM address to the file, line and column of the instruction it
+// was assembled from, and every label to its definition, so a debugger can
+// show the source line for a given PC.
+pub fn source_map(assembly: &Assembly, file: &str) -> String {
+    let name = |index: usize| if index == 0 { file } else { assembly.files[index].name.as_str() };
+    let addresses: Vec<String> = assembly.spans.iter().enumerate()
+        .map(|(address, span)| format!(
+            "    {{\"address\": {}, {}}}",
+            address, location(name(assembly.word_files[address]), span)
+        ))
+        .collect();
+    let labels: Vec<String> = assembly.labels.iter()
+        .map(|label| format!(
+            "    {{\"name\": {}, \"address\": {}, {}}}",
+            json::string(&label.name), label.address, location(name(label.file), &label.span)
+        ))
+        .collect();
+
+    format!(
+        "{{\n  \"version\": 1,\n  \"file\": {},\n  \"addresses\": [\n{}\n  ],\n  \"labels\": [\n{}\n  ]\n}}\n",
+        json::string(file), addresses.join(",\n"), labels.join(",\n")
+    )
+}
+
+fn location(file: &str, span: &Span) -> String {
+    format!(
+        "\"file\": {}, \"line\": {}, \"column\": {}, \"length\": {}",
+        json::string(file), span.line, span.column, span.len
+    )
+}
@@ -0,0 +1,32 @@
+use crate::output::json;
+use crate::parser::compiler::Assembly;
+use crate::parser::parser::SymbolKind;
+
+// One `kind address name` row per symbol; labels hold ROM addresses,
+// variables RAM addresses from 16 and constants their value.
+pub fn sym_file(assembly: &Assembly) -> String {
+    assembly.symbols.iter()
+        .map(|symbol| format!("{:<10} {:>5}  {}\n", kind_name(symbol.kind), symbol.address, symbol.name))
+        .collect()
+}
+
+pub fn symbols_json(assembly: &Assembly) -> String {
+    let entries: Vec<String> = assembly.symbols.iter()
+        .map(|symbol| format!(
+            "    {{\"name\": {}, \"address\": {}, \"kind\": \"{}\"}}",
+            json::string(&symbol.name), symbol.address, kind_name(symbol.kind)
+        ))
+        .collect();
+
+    format!("{{\n  \"symbols\": [\n{}\n  ]\n}}\n", entries.join(",\n"))
+}
+
+fn kind_name(kind: SymbolKind) -> &'static str {
+    match kind {
+        SymbolKind::Predefined => "predefined",
+        SymbolKind::Label => "label",
+        SymbolKind::Variable => "variable",
+        SymbolKind::Constant => "constant",
+        SymbolKind::Extern => "extern",
+    }
+}
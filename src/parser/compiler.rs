@@ -1,18 +1,174 @@
-use crate::parser::tokenizer::Tokenizer;
-use crate::parser::parser::Parser;
+use crate::parser::tokenizer::{Token, Tokenizer};
+use crate::parser::parser::{Parser, Symbol, SymbolKind};
 use crate::parser::expression::Evaluate;
+use crate::parser::dialect::Dialect;
+use crate::parser::error::{AssembleError, Diagnostic, Diagnostics};
+use crate::parser::span::Span;
+use crate::preprocessor::{preprocess, SourceFile};
+use crate::preprocessor::include::{FileLoader, Loader};
+use std::collections::HashMap;
+use std::path::PathBuf;
 
-pub fn compile(source: String) -> String {
-    let tokens = Tokenizer::new().tokenize(source);
-    let expressions = Parser::new().parse(&tokens);
-    let mut buffer = String::new();
+#[derive(Clone)]
+pub struct CompileOptions {
+    // Assembly stops collecting diagnostics once this many have been reported.
+    pub max_errors: usize,
+    pub dialect: Dialect,
+    // The file being assembled, `.include`s are resolved next to it.
+    pub path: Option<PathBuf>,
+    // Searched in order for included files not found next to the includer.
+    pub include_paths: Vec<PathBuf>,
+    // Assemble a module for the linker: `.extern` labels are left at 0 and
+    // every A-instruction loading a label or variable is recorded.
+    pub relocatable: bool,
+    // Names `.if` and `.ifdef` can test besides the `.equ` constants.
+    pub defines: Vec<(String, i64)>,
+}
 
+impl Default for CompileOptions {
+    fn default() -> Self {
+        CompileOptions { max_errors: 20, dialect: Dialect::Strict, path: None, include_paths: vec![], relocatable: false, defines: vec![] }
+    }
+}
+
+// The assembled program together with what it was assembled from, for the
+// outputs that point back at the source (listings, symbol files, maps).
+pub struct Assembly {
+    pub source: String,
+    // One 16 character binary word per ROM address.
+    pub words: Vec<String>,
+    // Span of the instruction each word was assembled from, in the file at
+    // the same index of `word_files`.
+    pub spans: Vec<Span>,
+    pub word_files: Vec<usize>,
+    // The assembled file first, then every included file.
+    pub files: Vec<SourceFile>,
+    pub labels: Vec<Label>,
+    // The final symbol table: predefined symbols, labels and variables.
+    pub symbols: Vec<Symbol>,
+    // Extensions used by the source, reported in the strict dialect only.
+    pub warnings: Vec<Diagnostic>,
+    // A-instructions that load a symbol, and the labels named by `.export`.
+    pub references: Vec<Reference>,
+    pub exports: Vec<String>,
+}
+
+pub struct Reference {
+    pub address: usize,
+    pub symbol: String,
+}
+
+pub struct Label {
+    pub name: String,
+    pub address: u32,
+    pub span: Span,
+    pub file: usize,
+}
+
+impl Assembly {
+    pub fn to_hack(&self) -> String {
+        self.words.join("\n")
+    }
+
+    pub fn machine_words(&self) -> Vec<u16> {
+        self.words.iter().map(|word| u16::from_str_radix(word, 2).unwrap_or(0)).collect()
+    }
+}
+
+pub fn compile(source: String) -> Result<String, Vec<Diagnostic>> {
+    compile_with_options(source, &CompileOptions::default())
+}
+
+pub fn compile_with_options(source: String, options: &CompileOptions) -> Result<String, Vec<Diagnostic>> {
+    assemble(source, options).map(|assembly| assembly.to_hack())
+}
+
+pub fn assemble(source: String, options: &CompileOptions) -> Result<Assembly, Vec<Diagnostic>> {
+    assemble_with_loader(source, options, &FileLoader)
+}
+
+pub fn assemble_with_loader(source: String, options: &CompileOptions, loader: &dyn Loader) -> Result<Assembly, Vec<Diagnostic>> {
+    let mut diagnostics = Diagnostics::new(options.max_errors);
+    let expansion = preprocess(&source, options, loader, &mut diagnostics);
+    let file_names: Vec<String> = expansion.files.iter().map(|file| file.name.clone()).collect();
+    if diagnostics.has_errors() {
+        return Err(diagnostics.into_vec(&file_names));
+    }
+
+    let tokens = Tokenizer::with_dialect(options.dialect).tokenize(expansion.text.clone(), &mut diagnostics);
+    let mut parser = Parser::new();
+    let expressions = parser.parse(&tokens, &mut diagnostics);
+    let mut words = vec![];
+    let mut spans = vec![];
+    let mut word_files = vec![];
+    let mut references = vec![];
+
+    let mut warnings = vec![];
     for expression in expressions {
-        if buffer.len() > 0 {
-            buffer.push_str("\n");
+        if let Some(symbol) = &expression.symbol {
+            if !options.relocatable && parser.sym_table().kind(symbol) == Some(SymbolKind::Extern) {
+                diagnostics.push(Diagnostic::new(AssembleError::UnresolvedSymbol(symbol.clone()), expression.span)
+                    .with_note(format!("note: `{}` is declared `.extern`, assemble the module with `--object` and link it", symbol)));
+                continue;
+            }
+            references.push(Reference { address: words.len(), symbol: symbol.clone() });
+        }
+        match expression.evaluate(&mut warnings) {
+            Ok(expression_words) => {
+                for word in expression_words {
+                    words.push(word);
+                    spans.push(expansion.remap_span(expression.span));
+                    word_files.push(expansion.origin(expression.span).file);
+                }
+            },
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+
+    for warning in warnings {
+        diagnostics.push(warning);
+    }
+    let mut exports = vec![];
+    let mut exported: HashMap<&str, Span> = HashMap::new();
+    for token in &tokens {
+        if let Token::Export(name) = &token.node {
+            if let Some(previous) = exported.insert(name, token.span) {
+                let line = expansion.remap_span(previous).line;
+                diagnostics.push(Diagnostic::new(AssembleError::Syntax(format!("`{}` is already exported", name)), token.span)
+                    .with_note(format!("note: previously exported on line {}", line)));
+                continue;
+            }
+            match parser.sym_table().kind(name) {
+                Some(SymbolKind::Label) => exports.push(name.clone()),
+                _ => diagnostics.push(Diagnostic::new(AssembleError::UnresolvedSymbol(name.clone()), token.span)
+                    .with_note(String::from("note: only labels defined in the module can be exported"))),
+            }
         }
-        let res = expression.evaluate();
-        buffer.push_str(res.as_str());
     }
-    buffer
-}
\ No newline at end of file
+    // Point everything found in the expanded text back at the source.
+    diagnostics.map(|diagnostic| expansion.remap(diagnostic));
+    if diagnostics.has_errors() {
+        return Err(diagnostics.into_vec(&file_names));
+    }
+    let warnings = match options.dialect {
+        Dialect::Strict => diagnostics.take_warnings(&file_names),
+        Dialect::Permissive => vec![],
+    };
+
+    let labels = tokens.iter().enumerate()
+        .filter_map(|(index, token)| match &token.node {
+            Token::JumpSymbol(name, address) => Some(Label {
+                name: String::from(parser.name(index, name)),
+                address: *address,
+                span: expansion.remap_span(token.span),
+                file: expansion.origin(token.span).file,
+            }),
+            _ => None,
+        })
+        .collect();
+
+    let symbols = parser.sym_table().symbols().to_vec();
+
+    let files = expansion.files;
+    Ok(Assembly { source, words, spans, word_files, files, labels, symbols, warnings, references, exports })
+}
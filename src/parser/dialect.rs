@@ -0,0 +1,37 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dialect {
+    // The Hack language as the course specifies it; every extension the
+    // assembler accepts is reported as a warning naming its rule.
+    Strict,
+    // The same language with the extensions accepted silently.
+    Permissive,
+}
+
+// The extensions over the specification, each with the name warnings use.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rule {
+    // `d=m;jgt`
+    CaseInsensitive,
+    // `D = M`, `@ 5`, `( LOOP )`
+    Whitespace,
+    // `# ...` and `/* ... */`
+    CommentStyle,
+    // `A+D`, `1+D`, `M|D`
+    CommutativeComp,
+    // `@0x4000`, `@0b1010`, `@'A'`, `@1_000`
+    LiteralSyntax,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule::CaseInsensitive => write!(f, "case-insensitive"),
+            Rule::Whitespace => write!(f, "whitespace"),
+            Rule::CommentStyle => write!(f, "comment-style"),
+            Rule::CommutativeComp => write!(f, "commutative-comp"),
+            Rule::LiteralSyntax => write!(f, "literal-syntax"),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::error::Error;
+use std::fmt;
+use crate::parser::dialect::Rule;
+use crate::parser::span::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Comp,
+    Dest,
+    Jump,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssembleError {
+    Lexical(String),
+    Syntax(String),
+    UnknownMnemonic(Field, String),
+    RepeatedRegister(String, char),
+    // A construct outside the nand2tetris specification, accepted as an extension.
+    NonStandard(Rule, String),
+    UnresolvedSymbol(String),
+    // A file that cannot be found, read or included again.
+    Include(String),
+    Redefinition(String),
+    ConstantOutOfRange(String),
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Field::Comp => write!(f, "comp"),
+            Field::Dest => write!(f, "dest"),
+            Field::Jump => write!(f, "jump"),
+        }
+    }
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssembleError::Lexical(message) | AssembleError::Syntax(message) | AssembleError::Include(message) => write!(f, "{}", message),
+            AssembleError::UnknownMnemonic(field, mnemonic) => write!(f, "unknown {} mnemonic `{}`", field, mnemonic),
+            AssembleError::RepeatedRegister(dest, register) => write!(f, "register `{}` appears more than once in dest `{}`", register, dest),
+            AssembleError::NonStandard(rule, message) => write!(f, "{} (rule `{}`)", message, rule),
+            AssembleError::UnresolvedSymbol(symbol) => write!(f, "unresolved symbol `{}`", symbol),
+            AssembleError::Redefinition(symbol) => write!(f, "symbol `{}` is already defined", symbol),
+            AssembleError::ConstantOutOfRange(constant) => write!(f, "constant `{}` is out of range", constant),
+        }
+    }
+}
+
+impl Error for AssembleError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub error: AssembleError,
+    pub span: Span,
+    pub severity: Severity,
+    // `help: ...` and `note: ...` lines printed under the source excerpt.
+    pub notes: Vec<String>,
+    // The included file the span is in, `None` for the file being assembled.
+    pub file: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(error: AssembleError, span: Span) -> Diagnostic {
+        Diagnostic { error, span, severity: Severity::Error, notes: vec![], file: None }
+    }
+
+    pub fn warning(error: AssembleError, span: Span) -> Diagnostic {
+        Diagnostic { error, span, severity: Severity::Warning, notes: vec![], file: None }
+    }
+
+    pub fn with_note(mut self, note: String) -> Diagnostic {
+        self.notes.push(note);
+        self
+    }
+
+    // Formats the diagnostic the way rustc does: the message, a `file:line:col`
+    // pointer and the offending source line with the span underlined.
+    pub fn render(&self, file: &str, source: &str) -> String {
+        let line_number = self.span.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let text = source.lines().nth(self.span.line.saturating_sub(1)).unwrap_or("");
+        let indent: String = text.chars()
+            .take(self.span.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        let level = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let mut rendered = format!(
+            "{}: {}\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}{}\n",
+            level, self.error,
+            gutter, file, self.span.line, self.span.column,
+            gutter,
+            line_number, text,
+            gutter, indent, "^".repeat(self.span.len.max(1))
+        );
+        for note in &self.notes {
+            rendered.push_str(&format!("{} = {}\n", gutter, note));
+        }
+        rendered
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.error)
+    }
+}
+
+impl Error for Diagnostic {}
+
+// Collects diagnostics across the pipeline. Only the first `limit` errors in
+// source order are reported, so a badly broken file does not bury them;
+// warnings are kept apart and never stop assembly.
+pub struct Diagnostics {
+    entries: Vec<Diagnostic>,
+    warnings: Vec<Diagnostic>,
+    limit: usize,
+}
+
+impl Diagnostics {
+    pub fn new(limit: usize) -> Diagnostics {
+        Diagnostics { entries: vec![], warnings: vec![], limit: limit.max(1) }
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        if diagnostic.severity == Severity::Warning {
+            self.warnings.push(diagnostic);
+        } else {
+            self.entries.push(diagnostic);
+        }
+    }
+
+    pub fn take_warnings(&mut self, files: &[String]) -> Vec<Diagnostic> {
+        let mut warnings = std::mem::take(&mut self.warnings);
+        sort(&mut warnings, files);
+        warnings
+    }
+
+    // Rewrites every diagnostic collected so far, errors and warnings alike.
+    pub fn map(&mut self, f: impl Fn(Diagnostic) -> Diagnostic) {
+        self.entries = self.entries.drain(..).map(&f).collect();
+        self.warnings = self.warnings.drain(..).map(&f).collect();
+    }
+
+    pub fn has_errors(&self) -> bool {
+        !self.entries.is_empty()
+    }
+
+    pub fn into_vec(mut self, files: &[String]) -> Vec<Diagnostic> {
+        sort(&mut self.entries, files);
+        self.entries.truncate(self.limit);
+        self.entries
+    }
+}
+
+// Groups diagnostics by file in the order of `files`, the names of the file
+// being assembled and then of every included file, and orders each group by
+// position. Diagnostics without a file belong to the file being assembled.
+fn sort(diagnostics: &mut [Diagnostic], files: &[String]) {
+    diagnostics.sort_by_key(|diagnostic| {
+        let file = diagnostic.file.as_ref().and_then(|file| files.iter().skip(1).position(|name| name == file));
+        (file.map_or(0, |index| index + 1), diagnostic.span.line, diagnostic.span.column)
+    });
+}
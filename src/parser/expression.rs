@@ -1,81 +1,71 @@
-use crate::parser::tokenizer::Token;
-use std::collections::HashMap;
-
-lazy_static! {
-    static ref HACK_COMP_MAP: HashMap<&'static str, &'static str> = [
-        ("0", "101010"),
-        ("1", "111111"),
-        ("-1", "111010"),
-        ("D", "001100"),
-        ("A", "110000"),
-        ("M", "110000"),
-        ("!D", "001101"),
-        ("!A", "110001"),
-        ("!M", "110001"),
-        ("-D", "001111"),
-        ("-A", "110011"),
-        ("-M", "110011"),
-        ("D+1", "011111"),
-        ("A+1", "110111"),
-        ("M+1", "110111"),
-        ("D-1", "001110"),
-        ("A-1", "110010"),
-        ("M-1", "110010"),
-        ("D+A", "000010"),
-        ("D+M", "000010"),
-        ("A+D", "000010"),
-        ("M+D", "000010"),
-        ("D-A", "010011"),
-        ("D-M", "010011"),
-        ("A-D", "000111"),
-        ("M-D", "000111"),
-        ("D&A", "000000"),
-        ("D&M", "000000"),
-        ("D|A", "010101"),
-        ("D|M", "010101")
-    ].iter().cloned().collect();
-    static ref HACK_DEST_MAP: HashMap<&'static str, &'static str> = [
-        ("M" , "001"),
-        ("D", "010"),
-        ("MD", "011"),
-        ("A", "100"),
-        ("AM", "101"),
-        ("AD", "110"),
-        ("AMD", "111"),
-    ].iter().cloned().collect();
-    static ref HACK_JMP_MAP: HashMap<&'static str, &'static str> = [
-        ("JNG", "000"),
-        ("JGT", "001"),
-        ("JEQ", "010"),
-        ("JGE", "011"),
-        ("JLT", "100"),
-        ("JNE", "101"),
-        ("JLE", "110"),
-        ("JMP", "111"),
-    ].iter().cloned().collect();
-}
+use crate::parser::tokenizer::{Token, MAX_A_CONSTANT};
+use crate::parser::error::{AssembleError, Diagnostic, Field};
+use crate::parser::span::{Span, Spanned};
+use crate::parser::dialect::Rule;
+use crate::parser::table::{dest_bits, lookup, normalize_comp, suggest};
 
 struct AsmCommandDescriptor<'a> {
     comp: &'a str,
-    dest: &'a str,
-    jump: &'a str,
+    dest: Option<&'a str>,
+    jump: Option<&'a str>,
 }
 
 impl<'a> AsmCommandDescriptor<'_> {
     pub fn new() -> AsmCommandDescriptor<'a> {
         AsmCommandDescriptor {
             comp: "",
-            dest: "",
-            jump: ""
+            dest: None,
+            jump: None
         }
     }
 
-    pub fn into_hack(self) -> String {
-        let raw_comp = HACK_COMP_MAP.get(self.comp).unwrap();
-        let raw_dest = if let Some(x) = HACK_DEST_MAP.get(self.dest) { x } else {"000"};
-        let raw_jump = HACK_JMP_MAP.get(self.jump).unwrap();
-        let a = if self.comp.contains("M") { 1 } else { 0 };
-        String::from(format!("111{}{}{}{}", a, raw_comp, raw_dest, raw_jump))
+    // Extensions the instruction relies on are added to `deviations`.
+    pub fn into_hack(self, deviations: &mut Vec<(Field, Rule, String)>) -> Result<String, AssembleError> {
+        let unknown_comp = |comp: &str| AssembleError::UnknownMnemonic(Field::Comp, String::from(comp));
+        let comp = case_insensitive(Field::Comp, self.comp, deviations, |comp| {
+            lookup(Field::Comp, comp).map(|_| String::from(comp)).ok_or_else(|| unknown_comp(comp))
+        })?;
+        let standard_comp = normalize_comp(&comp);
+        if standard_comp != comp {
+            let message = format!("`{}` is not a standard comp, the course writes `{}`", comp, standard_comp);
+            deviations.push((Field::Comp, Rule::CommutativeComp, message));
+        }
+        let raw_comp = lookup(Field::Comp, &standard_comp).ok_or_else(|| unknown_comp(self.comp))?;
+
+        let raw_dest = match self.dest {
+            Some(dest) => case_insensitive(Field::Dest, dest, deviations, dest_bits)?,
+            None => String::from("000"),
+        };
+        let raw_jump = match self.jump {
+            Some(jump) => case_insensitive(Field::Jump, jump, deviations, |jump| {
+                lookup(Field::Jump, jump).ok_or_else(|| AssembleError::UnknownMnemonic(Field::Jump, String::from(jump)))
+            })?,
+            None => "000",
+        };
+        Ok(format!("111{}{}{}", raw_comp, raw_dest, raw_jump))
+    }
+}
+
+// Retries a mnemonic that does not resolve in upper case, recording the
+// deviation; the error of the original spelling is kept otherwise.
+fn case_insensitive<T>(
+    field: Field,
+    mnemonic: &str,
+    deviations: &mut Vec<(Field, Rule, String)>,
+    resolve: impl Fn(&str) -> Result<T, AssembleError>
+) -> Result<T, AssembleError> {
+    let error = match resolve(mnemonic) {
+        Ok(resolved) => return Ok(resolved),
+        Err(error) => error,
+    };
+
+    let upper = mnemonic.to_uppercase();
+    match resolve(&upper) {
+        Ok(resolved) if upper != mnemonic => {
+            deviations.push((field, Rule::CaseInsensitive, format!("mnemonic `{}` is not upper case", mnemonic)));
+            Ok(resolved)
+        },
+        _ => Err(error),
     }
 }
 
@@ -87,66 +77,101 @@ pub enum  ExpressionType {
 
 pub struct Expression {
     pub e_type: ExpressionType,
-    pub tokens: Vec<Token>
+    pub tokens: Vec<Spanned<Token>>,
+    pub span: Span,
+    // The symbol an A-instruction was resolved from, for relocation.
+    pub symbol: Option<String>,
 }
 
 impl Expression {
-    pub fn new(e_type: ExpressionType, tokens: Vec<Token>) -> Expression {
+    pub fn new(e_type: ExpressionType, tokens: Vec<Spanned<Token>>) -> Expression {
+        let span = match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => first.span.to(last.span),
+            _ => Span::default()
+        };
+
         Expression {
             e_type,
-            tokens
+            tokens,
+            span,
+            symbol: None
+        }
+    }
+
+    // The span of the token holding `field`, falling back to the whole
+    // instruction.
+    fn field_span(&self, field: Field) -> Span {
+        self.tokens.iter()
+            .find(|token| matches!((field, &token.node),
+                (Field::Comp, Token::CCommand(_))
+                | (Field::Dest, Token::Destination(_))
+                | (Field::Jump, Token::Jump(_))
+            ))
+            .map(|token| token.span)
+            .unwrap_or(self.span)
+    }
+
+    fn diagnostic(&self, error: AssembleError) -> Diagnostic {
+        let span = match &error {
+            AssembleError::UnknownMnemonic(field, _) => self.field_span(*field),
+            AssembleError::RepeatedRegister(_, _) => self.field_span(Field::Dest),
+            _ => self.span,
+        };
+        let suggestion = match &error {
+            AssembleError::UnknownMnemonic(field, mnemonic) => suggest(*field, mnemonic),
+            _ => None,
+        };
+
+        let diagnostic = Diagnostic::new(error, span);
+        match suggestion {
+            Some(mnemonic) => diagnostic.with_note(format!("help: did you mean `{}`?", mnemonic)),
+            None => diagnostic,
         }
     }
 }
 
+// `A=!A`, completing the load of a constant wider than 15 bits.
+const INVERT_A: &str = "1110110001100000";
+
+// An expression assembles to one word, or two for a wide A-constant.
 pub trait Evaluate {
-    fn evaluate(&self) -> String;
+    fn evaluate(&self, warnings: &mut Vec<Diagnostic>) -> Result<Vec<String>, Diagnostic>;
 }
 
 impl Evaluate for Expression {
-    fn evaluate(&self) -> String {
-        match self.e_type {
+    fn evaluate(&self, warnings: &mut Vec<Diagnostic>) -> Result<Vec<String>, Diagnostic> {
+        let result = match self.e_type {
             ExpressionType::ACommand => {
-                for token in &self.tokens {
-                    if let Token::ACommandLiteral(x) = token {
-                        return format!("0{:015b}", x);
+                if let Some(Token::ACommandLiteral(x)) = self.tokens.first().map(|token| &token.node) {
+                    if *x > MAX_A_CONSTANT {
+                        Ok(vec![format!("0{:015b}", !*x & MAX_A_CONSTANT), String::from(INVERT_A)])
                     } else {
-                        panic!("Evaluation error")
+                        Ok(vec![format!("0{:015b}", x)])
                     }
+                } else {
+                    Err(AssembleError::Syntax(String::from("expected a constant in a-command")))
                 }
             },
-            ExpressionType::CCommand => {
+            ExpressionType::CCommand | ExpressionType::JCommand => {
                 let mut command = AsmCommandDescriptor::new();
                 for token in &self.tokens {
-                    if let Token::Destination(x) = token  {
-                        command.dest = x.as_str();
-                    } else {
-                        match token {
-                            Token::CCommand(raw_command) => {
-                                command.comp = raw_command.as_str();
-                                command.jump = "JNG";
-                            }
-                            Token::Jump(raw) => command.jump = raw.as_str(),
-                            _ => {}
-                        }
-                    }
-                }
-
-                return command.into_hack()
-            },
-            ExpressionType::JCommand => {
-                let mut command = AsmCommandDescriptor::new();
-                for token in &self.tokens {
-                    match token {
+                    match &token.node {
+                        Token::Destination(x) => command.dest = Some(x),
                         Token::CCommand(x) => command.comp = x,
-                        Token::Jump(x) => command.jump = x,
+                        Token::Jump(x) => command.jump = Some(x),
                         _ => {}
                     }
                 }
 
-                return command.into_hack()
+                let mut deviations = vec![];
+                let hack = command.into_hack(&mut deviations).map(|word| vec![word]);
+                for (field, rule, message) in deviations {
+                    warnings.push(Diagnostic::warning(AssembleError::NonStandard(rule, message), self.field_span(field)));
+                }
+                hack
             }
-        }
-        String::new()
+        };
+
+        result.map_err(|error| self.diagnostic(error))
     }
 }
@@ -1,4 +1,9 @@
+#[allow(clippy::module_inception)]
 pub mod parser;
 pub mod tokenizer;
 pub mod expression;
-pub mod compiler;
\ No newline at end of file
+pub mod table;
+pub mod compiler;
+pub mod error;
+pub mod span;
+pub mod dialect;
\ No newline at end of file
@@ -1,106 +1,290 @@
 use crate::parser::tokenizer::{Token};
 use std::collections::HashMap;
-use crate::parser::tokenizer::Token::{JumpSymbol, ACommandSymbol};
+use crate::parser::tokenizer::Token::{JumpSymbol, ACommandSymbol, Constant, Extern};
 use crate::parser::expression::{Expression, ExpressionType};
+use crate::parser::error::{AssembleError, Diagnostic, Diagnostics};
+use crate::parser::span::{Span, Spanned};
+
+pub const PREDEFINED_SYMBOLS: [(&str, u32); 23] = [
+    ("SP", 0),
+    ("LCL", 1),
+    ("ARG", 2),
+    ("THIS", 3),
+    ("THAT", 4),
+    ("R0", 0),
+    ("R1", 1),
+    ("R2", 2),
+    ("R3", 3),
+    ("R4", 4),
+    ("R5", 5),
+    ("R6", 6),
+    ("R7", 7),
+    ("R8", 8),
+    ("R9", 9),
+    ("R10", 10),
+    ("R11", 11),
+    ("R12", 12),
+    ("R13", 13),
+    ("R14", 14),
+    ("R15", 15),
+    ("SCREEN", 16384),
+    ("KBD", 24576),
+];
 
 pub struct Parser {
-    sym_table: SymTable
+    sym_table: SymTable,
+    // Full names of local labels and of the references to them, by token index.
+    names: HashMap<usize, String>
 }
 
 impl Parser {
     pub fn new() -> Parser {
         Parser {
-            sym_table: SymTable::new()
+            sym_table: SymTable::new(),
+            names: HashMap::new()
         }
     }
 
-    pub fn parse(&mut self, tokens: &Vec<Token>) -> Vec<Expression> {
-        self.register_symbols(tokens);
+    pub fn sym_table(&self) -> &SymTable {
+        &self.sym_table
+    }
+
+    // The symbol a label or A-instruction token stands for.
+    pub fn name<'a>(&'a self, index: usize, name: &'a str) -> &'a str {
+        self.names.get(&index).map_or(name, String::as_str)
+    }
+
+    pub fn parse(&mut self, tokens: &[Spanned<Token>], diagnostics: &mut Diagnostics) -> Vec<Expression> {
+        self.register_symbols(tokens, diagnostics);
 
         let mut i = 0;
         let mut expressions: Vec<Expression> = vec![];
         while i < tokens.len() {
-            match tokens.get(i).unwrap() {
-                Token::ACommandSymbol(s) => {
-                    if let Some(e) = self.sym_table.get(s.clone()) {
-                        expressions.push(Expression {
-                            e_type: ExpressionType::ACommand,
-                            tokens: vec![Token::ACommandLiteral(*e)]
-                        });
-                        i+=1;
-                    } else {
-                        panic!("Invalid symbol");
-                    }
-                },
-                Token::ACommandLiteral(e) => {
-                    expressions.push(Expression::new(ExpressionType::ACommand, vec![Token::ACommandLiteral(*e)]));
+            if let Err(diagnostic) = self.parse_expression(tokens, &mut i, &mut expressions) {
+                let line = diagnostic.span.line;
+                diagnostics.push(diagnostic);
+                while i < tokens.len() && tokens[i].span.line <= line {
                     i+=1;
-                },
-                Token::Destination(s) => {
-                    i+=1;
-                    if let Token::CCommand(x) = tokens.get(i).unwrap() {
-                        let expression = Expression::new(ExpressionType::CCommand, vec![Token::Destination(s.clone()), Token::CCommand(x.clone())]);
-                        expressions.push(expression);
-                        i+=1;
-                    } else {
-                        panic!("Unexpected token, expected c-command")
-                    }
-                },
-                Token::CCommand(x) => {
-                    let c_comand = x;
-                    i+=1;
-                    if let Some(Token::Jump(x)) = tokens.get(i) {
-                        let expression = Expression::new(ExpressionType::JCommand, vec![Token::CCommand(c_comand.clone()), Token::Jump(x.clone())]);
-                        expressions.push(expression);
-                        i+=1;
-                    } else {
-                        panic!("Unexpected token, expected jump command")
-                    }
-                },
-                Token::InstructionEnd | Token::JumpSymbol(_, _) => i+=1,
-                _ => panic!("Unexpected token")
+                }
             }
         }
 
         expressions
     }
 
-    fn register_symbols(&mut self, tokens: &Vec<Token>) {
-        // TODO: refactor, remove x2 loops
-        for token in tokens {
-            match token {
+    fn parse_expression(&mut self, tokens: &[Spanned<Token>], i: &mut usize, expressions: &mut Vec<Expression>) -> Result<(), Diagnostic> {
+        let current = tokens.get(*i).unwrap();
+        let span = current.span;
+        match &current.node {
+            Token::ACommandSymbol(s) => {
+                let s = &String::from(self.name(*i, s));
+                if let Some(e) = self.sym_table.get(s.clone()) {
+                    expressions.push(Expression {
+                        e_type: ExpressionType::ACommand,
+                        tokens: vec![Spanned::new(Token::ACommandLiteral(*e), span)],
+                        span,
+                        symbol: Some(s.clone())
+                    });
+                    *i+=1;
+                } else if let Some((number, direction)) = numeric_reference(s) {
+                    let error = Diagnostic::new(AssembleError::UnresolvedSymbol(s.clone()), span);
+                    return Err(error.with_note(format!("note: there is no `{}:` label {} this instruction", number, direction)));
+                } else {
+                    return Err(Diagnostic::new(AssembleError::UnresolvedSymbol(s.clone()), span));
+                }
+            },
+            Token::ACommandLiteral(e) => {
+                expressions.push(Expression::new(ExpressionType::ACommand, vec![Spanned::new(Token::ACommandLiteral(*e), span)]));
+                *i+=1;
+            },
+            Token::Destination(_) => {
+                *i+=1;
+                if let Some(next @ Spanned { node: Token::CCommand(_), .. }) = tokens.get(*i) {
+                    let mut expression_tokens = vec![current.clone(), next.clone()];
+                    *i+=1;
+                    if let Some(jump @ Spanned { node: Token::Jump(_), .. }) = tokens.get(*i) {
+                        expression_tokens.push(jump.clone());
+                        *i+=1;
+                    }
+                    expressions.push(Expression::new(ExpressionType::CCommand, expression_tokens));
+                } else {
+                    let error = AssembleError::Syntax(String::from("unexpected token, expected c-command"));
+                    return Err(Diagnostic::new(error, span));
+                }
+            },
+            Token::CCommand(_) => {
+                *i+=1;
+                if let Some(next @ Spanned { node: Token::Jump(_), .. }) = tokens.get(*i) {
+                    let expression = Expression::new(ExpressionType::JCommand, vec![current.clone(), next.clone()]);
+                    expressions.push(expression);
+                    *i+=1;
+                } else {
+                    expressions.push(Expression::new(ExpressionType::CCommand, vec![current.clone()]));
+                }
+            },
+            Token::InstructionEnd | Token::JumpSymbol(_, _) | Token::Constant(_, _)
+            | Token::Export(_) | Token::Extern(_) => *i+=1,
+            _ => return Err(Diagnostic::new(AssembleError::Syntax(String::from("unexpected token")), span))
+        }
+
+        Ok(())
+    }
+
+    fn register_symbols(&mut self, tokens: &[Spanned<Token>], diagnostics: &mut Diagnostics) {
+        self.names = local_names(tokens);
+
+        // Labels, constants and imported labels share a namespace, so they are
+        // registered in source order before any variable gets a RAM address.
+        let mut definitions: HashMap<String, Span> = HashMap::new();
+        for (index, token) in tokens.iter().enumerate() {
+            match &token.node {
                 JumpSymbol(x, address) => {
-                    if !self.sym_table.entries.contains_key(x.as_str()) {
-                        self.sym_table.set(x.clone(), *address);
+                    let x = String::from(self.name(index, x));
+                    if let Some(SymbolKind::Constant) | Some(SymbolKind::Extern) = self.sym_table.kind(&x) {
+                        diagnostics.push(redefinition(&x, definitions[&x], token.span));
+                    } else if !self.sym_table.entries.contains_key(&x) {
+                        self.sym_table.set(x.clone(), *address, SymbolKind::Label);
+                        definitions.insert(x, token.span);
                     }
                 },
-                _ => {}
+                Constant(x, _) | Extern(x) => {
+                    match self.sym_table.kind(x) {
+                        Some(SymbolKind::Predefined) => diagnostics.push(
+                            Diagnostic::new(AssembleError::Redefinition(x.clone()), token.span)
+                                .with_note(format!("note: `{}` is a predefined symbol", x))
+                        ),
+                        Some(_) => diagnostics.push(redefinition(x, definitions[x], token.span)),
+                        None => {
+                            // Imported labels are placed by the linker.
+                            let (value, kind) = match &token.node {
+                                Constant(_, value) => (*value, SymbolKind::Constant),
+                                _ => (0, SymbolKind::Extern),
+                            };
+                            self.sym_table.set(x.clone(), value, kind);
+                            definitions.insert(x.clone(), token.span);
+                        },
+                    }
+                },
+                _ => {},
             }
         }
 
-        for token in tokens {
-            match token {
-                ACommandSymbol(x) => {
-                    if !self.sym_table.entries.contains_key(x.as_str()) {
-                        self.sym_table.add(x.clone());
-                    }
-                },
-                _ => {}
+        for (index, token) in tokens.iter().enumerate() {
+            if let ACommandSymbol(x) = &token.node {
+                let x = self.name(index, x);
+                // A numeric reference without its label is an error, not a variable.
+                if !self.sym_table.entries.contains_key(x) && numeric_reference(x).is_none() {
+                    self.sym_table.add(String::from(x));
+                }
             }
         }
     }
 }
 
-struct SymTable {
-    entries: HashMap<String, u32>,
+// `.loop` belongs to the closest label before it that does not start with a
+// dot and is named `MULT.loop` after it. Numeric labels (`1:`) may be defined
+// any number of times; `@1b` refers to the closest one before, `@1f` to the
+// closest one after. Each definition gets a name of its own, `1:0`, `1:1`...
+fn local_names(tokens: &[Spanned<Token>]) -> HashMap<usize, String> {
+    let mut names = HashMap::new();
+    let mut numeric: HashMap<&str, Vec<(usize, String)>> = HashMap::new();
+    let mut scope = "";
+
+    for (index, token) in tokens.iter().enumerate() {
+        match &token.node {
+            JumpSymbol(x, _) if x.chars().all(|c| c.is_ascii_digit()) => {
+                let definitions = numeric.entry(x.as_str()).or_default();
+                let name = format!("{}:{}", x, definitions.len());
+                definitions.push((index, name.clone()));
+                names.insert(index, name);
+            },
+            JumpSymbol(x, _) | ACommandSymbol(x) if x.starts_with('.') => {
+                names.insert(index, format!("{}{}", scope, x));
+            },
+            JumpSymbol(x, _) => scope = x,
+            _ => {},
+        }
+    }
+
+    for (index, token) in tokens.iter().enumerate() {
+        if let ACommandSymbol(x) = &token.node {
+            let resolved = numeric_reference(x).and_then(|(number, direction)| {
+                let definitions = numeric.get(number)?;
+                match direction {
+                    "before" => definitions.iter().rev().find(|(definition, _)| *definition < index),
+                    _ => definitions.iter().find(|(definition, _)| *definition > index),
+                }
+            });
+            if let Some((_, name)) = resolved {
+                names.insert(index, name.clone());
+            }
+        }
+    }
+
+    names
+}
+
+// `1b` and `1f`, with the direction they look in.
+fn numeric_reference(symbol: &str) -> Option<(&str, &'static str)> {
+    let (number, direction) = match symbol.strip_suffix('b') {
+        Some(number) => (number, "before"),
+        None => (symbol.strip_suffix('f')?, "after"),
+    };
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((number, direction))
+}
+
+fn redefinition(symbol: &str, previous: Span, span: Span) -> Diagnostic {
+    Diagnostic::new(AssembleError::Redefinition(String::from(symbol)), span)
+        .with_note(format!("note: previously defined on line {}", previous.line))
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Parser::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Predefined,
+    Label,
+    Variable,
+    // Defined with `.equ`; the address is the value and takes no RAM.
+    Constant,
+    // Declared with `.extern`, the address is only known after linking.
+    Extern,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub address: u32,
+    pub kind: SymbolKind,
+}
+
+pub struct SymTable {
+    // Index of every name in `symbols`.
+    entries: HashMap<String, usize>,
+    // Every entry in the order it was defined: predefined symbols, labels and
+    // constants in source order, then variables by RAM address.
+    symbols: Vec<Symbol>,
     address: u32
 }
 
+impl Default for SymTable {
+    fn default() -> Self {
+        SymTable::new()
+    }
+}
 
 impl SymTable{
     pub fn new() -> SymTable {
         let mut  table = SymTable {
             entries: HashMap::new(),
+            symbols: vec![],
             address: 16
         };
         table.add_defaults();
@@ -108,41 +292,30 @@ impl SymTable{
     }
 
     fn add_defaults(&mut self) {
-        self.set(String::from("SP"), 0);
-        self.set(String::from("LCL"), 1);
-        self.set(String::from("ARG"), 2);
-        self.set(String::from("THIS"), 3);
-        self.set(String::from("THAT"), 4);
-        self.set(String::from("R0"), 0);
-        self.set(String::from("R1"), 1);
-        self.set(String::from("R2"), 2);
-        self.set(String::from("R3"), 3);
-        self.set(String::from("R4"), 4);
-        self.set(String::from("R5"), 5);
-        self.set(String::from("R6"), 6);
-        self.set(String::from("R7"), 7);
-        self.set(String::from("R8"), 8);
-        self.set(String::from("R9"), 9);
-        self.set(String::from("R10"), 10);
-        self.set(String::from("R11"), 11);
-        self.set(String::from("R12"), 12);
-        self.set(String::from("R13"), 13);
-        self.set(String::from("R14"), 14);
-        self.set(String::from("R15"), 15);
-        self.set(String::from("SCREEN"), 16384);
-        self.set(String::from("KBD"), 24576);
+        for (name, address) in PREDEFINED_SYMBOLS.iter() {
+            self.set(String::from(*name), *address, SymbolKind::Predefined);
+        }
     }
 
     pub fn get(&self, key: String) -> Option<&u32> {
-        self.entries.get(&key)
+        self.entries.get(&key).map(|&index| &self.symbols[index].address)
+    }
+
+    pub fn kind(&self, key: &str) -> Option<SymbolKind> {
+        self.entries.get(key).map(|&index| self.symbols[index].kind)
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
     }
 
     pub fn add(&mut self, key: String) {
-        self.set(key, self.address);
+        self.set(key, self.address, SymbolKind::Variable);
         self.address += 1;
     }
 
-    fn set(&mut self, key: String, value: u32) {
-        self.entries.insert(key, value);
+    fn set(&mut self, key: String, value: u32, kind: SymbolKind) {
+        self.entries.insert(key.clone(), self.symbols.len());
+        self.symbols.push(Symbol { name: key, address: value, kind });
     }
 }
\ No newline at end of file
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use crate::parser::error::{AssembleError, Field};
+
+// The Hack instruction set as data. Comp bits are the `a` bit followed by the
+// six `c` bits; every mnemonic is spelled the way the course specifies it,
+// operand orderings such as `A+D` or `M|D` are accepted through `lookup`.
+// A missing dest or jump field encodes as `000` and has no mnemonic.
+pub struct InstructionTable {
+    pub comp: &'static [(&'static str, &'static str)],
+    pub dest: &'static [(&'static str, &'static str)],
+    pub jump: &'static [(&'static str, &'static str)],
+}
+
+pub const HACK_INSTRUCTIONS: InstructionTable = InstructionTable {
+    comp: &[
+        ("0", "0101010"),
+        ("1", "0111111"),
+        ("-1", "0111010"),
+        ("D", "0001100"),
+        ("A", "0110000"),
+        ("M", "1110000"),
+        ("!D", "0001101"),
+        ("!A", "0110001"),
+        ("!M", "1110001"),
+        ("-D", "0001111"),
+        ("-A", "0110011"),
+        ("-M", "1110011"),
+        ("D+1", "0011111"),
+        ("A+1", "0110111"),
+        ("M+1", "1110111"),
+        ("D-1", "0001110"),
+        ("A-1", "0110010"),
+        ("M-1", "1110010"),
+        ("D+A", "0000010"),
+        ("D+M", "1000010"),
+        ("D-A", "0010011"),
+        ("D-M", "1010011"),
+        ("A-D", "0000111"),
+        ("M-D", "1000111"),
+        ("D&A", "0000000"),
+        ("D&M", "1000000"),
+        ("D|A", "0010101"),
+        ("D|M", "1010101"),
+    ],
+    dest: &[
+        ("M", "001"),
+        ("D", "010"),
+        ("MD", "011"),
+        ("A", "100"),
+        ("AM", "101"),
+        ("AD", "110"),
+        ("AMD", "111"),
+    ],
+    jump: &[
+        ("JGT", "001"),
+        ("JEQ", "010"),
+        ("JGE", "011"),
+        ("JLT", "100"),
+        ("JNE", "101"),
+        ("JLE", "110"),
+        ("JMP", "111"),
+    ],
+};
+
+// A malformed table fails the build rather than assembling wrong words.
+const _: () = HACK_INSTRUCTIONS.validate();
+
+lazy_static! {
+    static ref COMP_MAP: HashMap<&'static str, &'static str> = HACK_INSTRUCTIONS.comp.iter().cloned().collect();
+    static ref DEST_MAP: HashMap<&'static str, &'static str> = HACK_INSTRUCTIONS.dest.iter().cloned().collect();
+    static ref JMP_MAP: HashMap<&'static str, &'static str> = HACK_INSTRUCTIONS.jump.iter().cloned().collect();
+}
+
+impl InstructionTable {
+    pub fn entries(&self, field: Field) -> &'static [(&'static str, &'static str)] {
+        match field {
+            Field::Comp => self.comp,
+            Field::Dest => self.dest,
+            Field::Jump => self.jump,
+        }
+    }
+
+    const fn validate(&self) {
+        validate_field(self.comp, 7);
+        validate_field(self.dest, 3);
+        validate_field(self.jump, 3);
+
+        let mut i = 0;
+        while i < self.comp.len() {
+            let (mnemonic, bits) = self.comp[i];
+            let uses_a = contains(mnemonic, b'A');
+            let uses_m = contains(mnemonic, b'M');
+            assert!(!(uses_a && uses_m), "a comp mnemonic reads either A or M");
+            assert!(uses_m == (bits.as_bytes()[0] == b'1'), "the `a` bit must be set exactly for M operands");
+            i += 1;
+        }
+    }
+}
+
+const fn validate_field(entries: &[(&str, &str)], width: usize) {
+    let mut i = 0;
+    while i < entries.len() {
+        let bits = entries[i].1.as_bytes();
+        assert!(bits.len() == width, "instruction table entry has the wrong number of bits");
+        let mut j = 0;
+        while j < bits.len() {
+            assert!(bits[j] == b'0' || bits[j] == b'1', "instruction table bits must be `0` or `1`");
+            j += 1;
+        }
+
+        let mut k = i + 1;
+        while k < entries.len() {
+            assert!(!str_eq(entries[i].0, entries[k].0), "duplicate mnemonic in the instruction table");
+            assert!(!str_eq(entries[i].1, entries[k].1), "duplicate bits in the instruction table");
+            k += 1;
+        }
+        i += 1;
+    }
+}
+
+const fn str_eq(left: &str, right: &str) -> bool {
+    let (left, right) = (left.as_bytes(), right.as_bytes());
+    if left.len() != right.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < left.len() {
+        if left[i] != right[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn contains(text: &str, byte: u8) -> bool {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == byte {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+pub fn lookup(field: Field, mnemonic: &str) -> Option<&'static str> {
+    let map: &HashMap<&'static str, &'static str> = match field {
+        Field::Comp => &COMP_MAP,
+        Field::Dest => &DEST_MAP,
+        Field::Jump => &JMP_MAP,
+    };
+    match field {
+        Field::Comp => map.get(normalize_comp(mnemonic).as_str()).copied(),
+        _ => map.get(mnemonic).copied(),
+    }
+}
+
+// Destinations are a set of registers, so any ordering (`DM`, `MA`, `DAM`)
+// is accepted; each letter contributes the bits of its single register entry.
+pub fn dest_bits(dest: &str) -> Result<String, AssembleError> {
+    if dest.is_empty() {
+        return Err(AssembleError::Syntax(String::from("expected a destination before `=`")));
+    }
+
+    let mut bits = 0;
+    let mut seen = vec![];
+    for register in dest.chars() {
+        let register_bits = lookup(Field::Dest, &register.to_string())
+            .map(|bits| u8::from_str_radix(bits, 2).unwrap_or(0))
+            .ok_or_else(|| AssembleError::UnknownMnemonic(Field::Dest, String::from(dest)))?;
+        if seen.contains(&register) {
+            return Err(AssembleError::RepeatedRegister(String::from(dest), register));
+        }
+        seen.push(register);
+        bits |= register_bits;
+    }
+
+    Ok(format!("{:03b}", bits))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DestStyle {
+    // `MD`, `AMD`, as in the first edition of the book.
+    Classic,
+    // `DM`, `ADM`, as in the second edition and its tools.
+    Modern,
+}
+
+pub fn dest_spelling(dest: &'static str, style: DestStyle) -> &'static str {
+    match (style, dest) {
+        (DestStyle::Modern, "MD") => "DM",
+        (DestStyle::Modern, "AMD") => "ADM",
+        _ => dest,
+    }
+}
+
+// `A+D`, `1+M`, `M|D`... are rewritten to the operand order of the table;
+// only `+`, `&` and `|` commute.
+pub fn normalize_comp(comp: &str) -> String {
+    let chars: Vec<char> = comp.chars().collect();
+    if COMP_MAP.contains_key(comp) || chars.len() != 3 || !"+&|".contains(chars[1]) {
+        return String::from(comp);
+    }
+
+    let swapped: String = [chars[2], chars[1], chars[0]].iter().collect();
+    if COMP_MAP.contains_key(swapped.as_str()) { swapped } else { String::from(comp) }
+}
+
+// The closest known mnemonic, ignoring case, if it is close enough to be a
+// plausible typo.
+pub fn suggest(field: Field, mnemonic: &str) -> Option<&'static str> {
+    let wanted = mnemonic.to_uppercase();
+    let len = wanted.chars().count();
+    HACK_INSTRUCTIONS.entries(field).iter()
+        .map(|(candidate, _)| (levenshtein(&wanted, candidate), *candidate))
+        .filter(|(distance, _)| *distance == 0 || (*distance <= (len / 3).max(1) && *distance < len))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn levenshtein(left: &str, right: &str) -> usize {
+    let right: Vec<char> = right.chars().collect();
+    let mut previous: Vec<usize> = (0..=right.len()).collect();
+
+    for (i, l) in left.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, r) in right.iter().enumerate() {
+            let cost = if l == *r { 0 } else { 1 };
+            current.push((previous[j] + cost).min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[right.len()]
+}
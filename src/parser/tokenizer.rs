@@ -1,10 +1,15 @@
-use std::str::FromStr;
+use crate::parser::dialect::{Dialect, Rule};
+use crate::parser::error::{AssembleError, Diagnostic, Diagnostics};
+use crate::parser::span::{Span, Spanned};
+
+pub const MAX_A_CONSTANT: u32 = 32767;
 
 lazy_static! {
     static ref ALLOWED_SPECIAL_CHAR: Vec<char> = vec!['.', '_', '$'];
     static ref ALLOWED_OPERATIONS: Vec<char> = vec!['!', '+', '-', '~', '&', '|'];
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     JumpSymbol(String, u32),
     InstructionEnd,
@@ -13,12 +18,21 @@ pub enum Token {
     Jump(String),
     Destination(String),
     CCommand(String),
+    // `.equ NAME VALUE` or `.define NAME VALUE`.
+    Constant(String, u32),
+    // `.export LABEL`, making a label visible to other modules.
+    Export(String),
+    // `.extern LABEL`, a label exported by another module.
+    Extern(String),
 }
 
 pub struct Tokenizer {
     raw: Vec<char>,
+    positions: Vec<(usize, usize)>,
     current_index: usize,
-    line: u32
+    rom_address: u32,
+    dialect: Dialect,
+    warnings: Vec<Diagnostic>
 }
 
 trait CharToken {
@@ -36,71 +50,169 @@ impl CharToken for char {
     }
 }
 
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Tokenizer::new()
+    }
+}
+
 impl Tokenizer {
     pub fn new() -> Tokenizer {
-        Tokenizer { raw: vec![], current_index: 0, line: 0 }
+        Tokenizer::with_dialect(Dialect::Strict)
     }
 
-    pub fn tokenize(&mut self, source: String) -> Vec<Token> {
-        self.raw = source.split("")
-            .filter(|s| *s != "" && *s != " ")
-            .filter(|s| !s.is_empty())
-            .map(|s| char::from_str(s).unwrap())
-            .collect();
+    pub fn with_dialect(dialect: Dialect) -> Tokenizer {
+        Tokenizer { raw: vec![], positions: vec![], current_index: 0, rom_address: 0, dialect, warnings: vec![] }
+    }
 
-        let mut tokens: Vec<Token> = vec![];
+    pub fn tokenize(&mut self, source: String, diagnostics: &mut Diagnostics) -> Vec<Spanned<Token>> {
+        self.raw = vec![];
+        self.positions = vec![];
+        self.current_index = 0;
+        self.rom_address = 0;
+        self.warnings = vec![];
+
+        let (mut line, mut column) = (1, 1);
+        for c in source.chars() {
+            if c == '\n' || !c.is_whitespace() {
+                self.raw.push(c);
+                self.positions.push((line, column));
+            }
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+        self.positions.push((line, column));
+
+        let mut tokens: Vec<Spanned<Token>> = vec![];
 
         while self.has_next() {
-            self.scan(&mut tokens);
-            if self.has_next() {
-                self.advance();
+            if let Err(diagnostic) = self.scan(&mut tokens) {
+                diagnostics.push(diagnostic);
+                self.move_until_new_line();
             }
         }
+        for warning in self.warnings.drain(..) {
+            diagnostics.push(warning);
+        }
 
         tokens
     }
 
-    fn scan(&mut self, tokens: &mut Vec<Token>) {
-       match self.current() {
-            c if c == '@' => {
-                let first_char = self.advance();
-                let buffer = self.scan_a_command();
+    fn scan(&mut self, tokens: &mut Vec<Spanned<Token>>) -> Result<(), Diagnostic> {
+        let start = self.current_index;
 
-                let token = if first_char.is_digit(10) {
-                    let literal = u32::from_str(buffer.as_str()).unwrap();
-                    Token::ACommandLiteral(literal)
+        match self.current() {
+            '@' => {
+                let negative = self.advance() == '-';
+                if negative {
+                    self.advance();
+                }
+                let first_char = self.current();
+                let buffer = self.scan_a_command();
+                let what = if first_char.is_ascii_digit() || negative { "constant" } else { "symbol" };
+                self.check_unbroken(start + 1, self.current_index, what)?;
+
+                let token = if first_char == '\'' && !negative {
+                    Token::ACommandLiteral(self.scan_char_literal()?)
+                } else if buffer.is_empty() {
+                    return Err(self.error_at(start, AssembleError::Syntax(String::from("expected a constant or symbol after `@`"))));
+                } else if is_numeric_reference(&buffer) && !negative {
+                    Token::ACommandSymbol(buffer)
+                } else if first_char.is_ascii_digit() {
+                    let (value, extended) = parse_literal(&buffer).map_err(|e| self.error_from(start + 1, e))?;
+                    if extended {
+                        self.warn(start + 1, Rule::LiteralSyntax, "hex, binary and `_` separated constants are not standard Hack");
+                    }
+                    Token::ACommandLiteral(self.a_constant(start, negative, value)?)
+                } else if negative {
+                    return Err(self.error_from(start + 1, AssembleError::Syntax(String::from("expected a constant after `@-`"))));
                 } else {
                     Token::ACommandSymbol(buffer)
                 };
 
-                tokens.push(token);
-                self.line +=1;
-                self.move_until_new_line();
+                // Wide constants take two instructions, see `a_constant`.
+                self.rom_address += match token {
+                    Token::ACommandLiteral(word) if word > MAX_A_CONSTANT => 2,
+                    _ => 1,
+                };
+                tokens.push(Spanned::new(token, self.span_from(start)));
+                // `@' '` has a space of its own.
+                if first_char != '\'' {
+                    self.check_spacing(start);
+                }
+                self.expect_line_end()?;
             },
-            c if c.is_alphanumeric() => {
-                let dest_buffer = self.scan_c_dest();
+            c if c.is_ascii_digit() && self.at_numeric_label() => {
+                let mut buffer = String::new();
+                while self.current() != ':' {
+                    buffer.push(self.current());
+                    self.advance();
+                }
+                self.advance();
+                self.check_unbroken(start, self.current_index, "label")?;
+                tokens.push(Spanned::new(Token::JumpSymbol(buffer, self.rom_address), self.span_from(start)));
+                self.expect_line_end()?;
+            },
+            c if c.is_alphanumeric() || c.is_operational() => {
+                let dest_buffer = self.scan_c_comp();
+                let dest_span = self.span_from(start);
+                self.check_unbroken(start, self.current_index, "mnemonic")?;
                 let separator = self.current();
+                // The specification lets both dest and jump be empty, which
+                // leaves a bare comp.
+                if separator != '=' && separator != ';' {
+                    tokens.push(Spanned::new(Token::CCommand(dest_buffer), dest_span));
+                    self.rom_address += 1;
+                    self.check_spacing(start);
+                    return self.expect_line_end();
+                }
+                let comp_start = self.current_index + 1;
                 self.advance();
                 let comp_buffer = self.scan_c_comp();
+                let comp_span = self.span_from(comp_start);
+                self.check_unbroken(comp_start, self.current_index, "mnemonic")?;
 
                 if separator == ';' {
-                    tokens.push(Token::CCommand(dest_buffer));
-                    tokens.push(Token::Jump(comp_buffer));
+                    tokens.push(Spanned::new(Token::CCommand(dest_buffer), dest_span));
+                    tokens.push(Spanned::new(Token::Jump(comp_buffer), comp_span));
                 } else {
-                    tokens.push(Token::Destination(dest_buffer));
-                    tokens.push(Token::CCommand(comp_buffer));
+                    tokens.push(Spanned::new(Token::Destination(dest_buffer), dest_span));
+                    tokens.push(Spanned::new(Token::CCommand(comp_buffer), comp_span));
+
+                    // `dest=comp;jump`
+                    if self.current() == ';' {
+                        let jump_start = self.current_index + 1;
+                        self.advance();
+                        let jump_buffer = self.scan_c_comp();
+                        self.check_unbroken(jump_start, self.current_index, "mnemonic")?;
+                        tokens.push(Spanned::new(Token::Jump(jump_buffer), self.span_from(jump_start)));
+                    }
                 };
-                self.line +=1;
-                self.move_until_new_line();
+                self.rom_address +=1;
+                self.check_spacing(start);
+                self.expect_line_end()?;
             },
-           ch if ch == '(' => {
-               let buffer = self.scan_jump_label();
-               tokens.push(Token::JumpSymbol(buffer, self.line));
-               self.move_until_new_line();
-           },
-            ch if ch == '/' && self.peek() == '/' => self.move_until_new_line(),
-            _ => {}
+            '(' => {
+                let buffer = self.scan_jump_label()?;
+                tokens.push(Spanned::new(Token::JumpSymbol(buffer, self.rom_address), self.span_from(start)));
+                self.check_spacing(start);
+                self.expect_line_end()?;
+            },
+            '/' | '#' if self.skip_comment()? => {},
+            '.' => {
+                let token = self.scan_directive()?;
+                tokens.push(Spanned::new(token, self.span_from(start)));
+                self.expect_line_end()?;
+            },
+            c if c.is_whitespace() => { self.advance(); },
+            c => return Err(self.error_at(start, AssembleError::Lexical(format!("unexpected character `{}`", c)))),
         };
+
+        Ok(())
     }
 
     fn scan_a_command(&mut self) -> String {
@@ -109,27 +221,48 @@ impl Tokenizer {
 
         while current_char.is_alphanumeric() || current_char.is_special() {
             buffer.push(current_char);
-            if !self.has_next() {
-                break
-            }
             current_char = self.advance();
         }
 
         buffer
     }
 
-    fn scan_c_dest(&mut self) -> String {
-        let mut buffer = String::new();
-        let mut current_char = self.current();
-
-        while current_char != ';' && current_char != '=' {
-            self.throw_if_newline();
-
-            buffer.push(current_char);
-            if self.has_next() { current_char = self.advance(); } else { break; }
+    // `'A'` is the code of the key on the Hack keyboard: printable ASCII as
+    // is, `'\n'` (newline) 128, `'\b'` (backspace) 129 and `'\e'` (escape) 140.
+    fn scan_char_literal(&mut self) -> Result<u32, Diagnostic> {
+        let start = self.current_index;
+        let mut current_char = self.advance();
+        let mut code = current_char as u32;
+
+        // Whitespace is dropped from `raw`, so `' '` shows up as a gap between the quotes.
+        let (line, column) = self.positions[start];
+        if current_char == '\'' && self.positions[self.current_index] == (line, column + 2) {
+            code = ' ' as u32;
+        } else if current_char == '\\' {
+            current_char = self.advance();
+            code = match current_char {
+                'n' => 128,
+                'b' => 129,
+                'e' => 140,
+                '\\' | '\'' => current_char as u32,
+                _ => return Err(self.error_from(start, AssembleError::Lexical(format!("unknown escape `\\{}`", current_char)))),
+            };
+            self.advance();
+        } else if current_char == '\n' {
+            return Err(self.error_from(start, AssembleError::Syntax(String::from("unterminated character constant"))));
+        } else if !(' '..='~').contains(&current_char) {
+            self.advance();
+            return Err(self.error_from(start, AssembleError::Lexical(format!("`{}` has no Hack keyboard code", current_char))));
+        } else {
+            self.advance();
         }
 
-        buffer
+        if self.current() != '\'' {
+            return Err(self.error_from(start, AssembleError::Syntax(String::from("unterminated character constant, expected `'`"))));
+        }
+        self.advance();
+        self.warn(start, Rule::LiteralSyntax, "character constants are not standard Hack");
+        Ok(code)
     }
 
     fn scan_c_comp(&mut self) -> String {
@@ -137,70 +270,281 @@ impl Tokenizer {
 
         let mut buffer = String::new();
         while current_char.is_alphanumeric() || current_char.is_operational() {
-            self.throw_if_newline();
             buffer.push(current_char);
-
-            if self.has_next() { current_char = self.advance(); } else { break; }
+            current_char = self.advance();
         }
         buffer
     }
 
-    fn scan_jump_label(&mut self) -> String {
+    fn scan_jump_label(&mut self) -> Result<String, Diagnostic> {
+        let start = self.current_index;
         let mut buffer = String::new();
+        let mut current_char = self.advance();
 
-        while self.has_next() && self.peek() != ')' {
-            let current_char = self.advance();
-            self.throw_if_newline();
+        while current_char != ')' {
+            if current_char == '\n' {
+                let error = AssembleError::Syntax(String::from("unterminated label, expected `)`"));
+                return Err(Diagnostic::new(error, self.span_from(start)));
+            }
 
             buffer.push(current_char);
+            current_char = self.advance();
         }
+        self.check_unbroken(start + 1, self.current_index, "label")?;
+        self.advance();
 
-        if !self.has_next() || self.peek() != ')' {
-            panic!("Unexpected symbol");
+        if buffer.is_empty() {
+            return Err(Diagnostic::new(AssembleError::Syntax(String::from("empty label")), self.span_from(start)));
         }
 
-        buffer
+        Ok(buffer)
     }
 
-    fn throw_if_newline(&mut self) {
-        if self.current() == '\n' {
-            panic!("Unexpected new line char");
+    fn scan_directive(&mut self) -> Result<Token, Diagnostic> {
+        let start = self.current_index;
+        let directive = self.scan_word();
+
+        match directive.as_str() {
+            ".equ" | ".define" => {
+                let name_start = self.current_index;
+                let name = self.scan_word();
+                if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+                    let message = format!("expected a symbol name after `{}`", directive);
+                    return Err(self.error_at(name_start, AssembleError::Syntax(message)));
+                }
+                if self.current() == ',' {
+                    self.advance();
+                }
+
+                let value_start = self.current_index;
+                let value = self.scan_word();
+                if !value.starts_with(|c: char| c.is_ascii_digit()) {
+                    let message = format!("expected a constant value for `{}`", name);
+                    return Err(self.error_at(value_start, AssembleError::Syntax(message)));
+                }
+                let (value, _) = parse_literal(&value).map_err(|e| self.error_from(value_start, e))?;
+                if value > MAX_A_CONSTANT {
+                    let text = self.raw[value_start..self.current_index].iter().collect();
+                    return Err(self.error_from(value_start, AssembleError::ConstantOutOfRange(text))
+                        .with_note(format!("help: constants load into A-instructions, so they must be between 0 and {}", MAX_A_CONSTANT)));
+                }
+
+                Ok(Token::Constant(name, value))
+            },
+            ".export" | ".extern" => {
+                let name_start = self.current_index;
+                let name = self.scan_word();
+                if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+                    let message = format!("expected a label after `{}`", directive);
+                    return Err(self.error_at(name_start, AssembleError::Syntax(message)));
+                }
+
+                Ok(if directive == ".export" { Token::Export(name) } else { Token::Extern(name) })
+            },
+            _ => Err(self.error_from(start, AssembleError::Lexical(format!("unknown directive `{}`", directive)))),
         }
     }
 
-    fn move_until_new_line(&mut self) {
-        if self.current() == '\n' {
-            return;
+    // Directive operands are separated by whitespace. Whitespace is dropped
+    // from `raw`, so a word also ends where the columns stop being contiguous.
+    fn scan_word(&mut self) -> String {
+        while self.current() != '\n' && self.current().is_whitespace() {
+            self.advance();
         }
-        while self.has_next() && self.peek() != '\n' {
+
+        let mut buffer = String::new();
+        let mut previous: Option<(usize, usize)> = None;
+        while self.current().is_alphanumeric() || self.current().is_special() {
+            let position = self.positions[self.current_index];
+            if let Some((line, column)) = previous {
+                if position != (line, column + 1) {
+                    break;
+                }
+            }
+            buffer.push(self.current());
+            previous = Some(position);
             self.advance();
         }
+
+        buffer
     }
 
-    fn current(&mut self) -> char {
-         self.raw[self.current_index]
+    // `1:`, a numeric local label.
+    fn at_numeric_label(&self) -> bool {
+        let digits = self.raw[self.current_index..].iter().take_while(|c| c.is_ascii_digit()).count();
+        self.raw.get(self.current_index + digits) == Some(&':')
     }
 
-    fn advance(&mut self) -> char {
-        if !self.has_next() {
-            panic!("Out of range");
+    fn expect_line_end(&mut self) -> Result<(), Diagnostic> {
+        let mut current_char = self.current();
+
+        while current_char != '\n' {
+            if self.skip_comment()? {
+                current_char = self.current();
+                continue;
+            } else if !current_char.is_whitespace() {
+                let message = format!("unexpected `{}` after instruction", current_char);
+                return Err(self.error_at(self.current_index, AssembleError::Syntax(message)));
+            }
+            current_char = self.advance();
         }
 
-        self.current_index += 1;
-        self.raw[self.current_index]
+        Ok(())
     }
 
-    fn peek(&self) -> char {
-        if !self.has_next() {
-            panic!("Out of range");
+    // A-instructions load 15 bits. The permissive dialect also accepts any
+    // other 16-bit word, negative constants included, and expands it into
+    // `@!word` followed by `A=!A`; the strict dialect rejects it.
+    fn a_constant(&self, start: usize, negative: bool, value: u32) -> Result<u32, Diagnostic> {
+        let word = match negative {
+            true if value <= 32768 => Some((65536 - value) % 65536),
+            false if value <= 65535 => Some(value),
+            _ => None,
+        };
+        let text = self.raw[start + 1..self.current_index].iter().collect();
+
+        match word {
+            Some(word) if word <= MAX_A_CONSTANT || self.dialect == Dialect::Permissive => Ok(word),
+            Some(_) => Err(self.error_from(start + 1, AssembleError::ConstantOutOfRange(text))
+                .with_note(String::from("help: A-instructions load 0 to 32767, the permissive dialect loads other 16-bit values in two instructions"))),
+            None => Err(self.error_from(start + 1, AssembleError::ConstantOutOfRange(text))
+                .with_note(String::from("note: constants must fit in 16 bits"))),
+        }
+    }
+
+    // Skips a comment starting at the current character, if there is one.
+    // Only `//` comments are standard Hack.
+    fn skip_comment(&mut self) -> Result<bool, Diagnostic> {
+        let start = self.current_index;
+        match (self.current(), self.peek()) {
+            ('/', '/') => self.move_until_new_line(),
+            ('#', _) => {
+                self.move_until_new_line();
+                self.warn(start, Rule::CommentStyle, "`#` comments are not standard Hack, use `//`");
+            },
+            ('/', '*') => {
+                self.advance();
+                self.advance();
+                while !(self.current() == '*' && self.peek() == '/') {
+                    if !self.has_next() {
+                        return Err(self.error_at(start, AssembleError::Syntax(String::from("unterminated comment, expected `*/`"))));
+                    }
+                    self.advance();
+                }
+                self.advance();
+                self.advance();
+                self.warn(start, Rule::CommentStyle, "`/* */` comments are not standard Hack, use `//`");
+            },
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // Whitespace may separate the parts of an instruction but not split a
+    // number, symbol or mnemonic: `raw[start..end]` must be adjacent in the
+    // source, in either dialect.
+    fn check_unbroken(&self, start: usize, end: usize, what: &str) -> Result<(), Diagnostic> {
+        let mut text = String::new();
+        let mut broken = false;
+        for i in start..end {
+            let (line, column) = self.positions[i.max(start + 1) - 1];
+            if i > start && self.positions[i] != (line, column + 1) {
+                text.push(' ');
+                broken = true;
+            }
+            text.push(self.raw[i]);
+        }
+        if !broken {
+            return Ok(());
+        }
+
+        let (line, column) = self.positions[start];
+        let (_, end_column) = self.positions[end - 1];
+        let message = format!("whitespace splits the {} `{}`", what, text);
+        Err(Diagnostic::new(AssembleError::Syntax(message), Span::new(line, column, end_column - column + 1)))
+    }
+
+    // The characters consumed since `start` should sit next to each other;
+    // skipped whitespace shows up as a gap between their columns.
+    fn check_spacing(&mut self, start: usize) {
+        let end = self.current_index.max(start + 1) - 1;
+        let ((line, column), (end_line, end_column)) = (self.positions[start], self.positions[end]);
+        if line == end_line && end_column - column > end - start {
+            self.warn(start, Rule::Whitespace, "whitespace inside an instruction is not standard Hack");
+        }
+    }
+
+    fn warn(&mut self, start: usize, rule: Rule, message: &str) {
+        let warning = Diagnostic::warning(AssembleError::NonStandard(rule, String::from(message)), self.span_from(start));
+        self.warnings.push(warning);
+    }
+
+    fn move_until_new_line(&mut self) {
+        while self.current() != '\n' {
+            self.advance();
         }
+    }
+
+    // Span of the characters consumed since `start`, excluding the current one.
+    fn span_from(&self, start: usize) -> Span {
+        let (line, column) = self.positions[start];
+        let end = self.current_index.max(start + 1) - 1;
+        let (end_line, end_column) = self.positions[end];
+        let len = if end_line == line { end_column - column + 1 } else { 1 };
+        Span::new(line, column, len)
+    }
+
+    fn error_at(&self, index: usize, error: AssembleError) -> Diagnostic {
+        let (line, column) = self.positions[index];
+        Diagnostic::new(error, Span::new(line, column, 1))
+    }
+
+    fn error_from(&self, start: usize, error: AssembleError) -> Diagnostic {
+        Diagnostic::new(error, self.span_from(start))
+    }
 
-        self.raw[self.current_index + 1]
+    // The end of the source reads as a final new line, so every scanner stops there.
+    fn current(&self) -> char {
+        self.raw.get(self.current_index).copied().unwrap_or('\n')
+    }
+
+    fn advance(&mut self) -> char {
+        if self.has_next() {
+            self.current_index += 1;
+        }
+        self.current()
+    }
+
+    fn peek(&self) -> char {
+        self.raw.get(self.current_index + 1).copied().unwrap_or('\n')
     }
 
     fn has_next(&self) -> bool {
-        self.current_index < self.raw.len() - 1
-        // self.has_next_idx(1)
+        self.current_index < self.raw.len()
     }
 }
 
+// `@1b` and `@1f` refer to the numeric label `1:` before or after them; `0b`
+// alone is not a binary constant.
+fn is_numeric_reference(buffer: &str) -> bool {
+    let digits = buffer.chars().take_while(|c| c.is_ascii_digit()).count();
+    digits > 0 && digits + 1 == buffer.len() && (buffer.ends_with('b') || buffer.ends_with('f'))
+}
+
+// Decimal, `0x` hexadecimal or `0b` binary digits, optionally separated by
+// `_`; the flag tells whether any of those extensions was used.
+pub fn parse_literal(buffer: &str) -> Result<(u32, bool), AssembleError> {
+    let digits: String = buffer.chars().filter(|c| *c != '_').collect();
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x") | Some("0X") => (16, &digits[2..]),
+        Some("0b") | Some("0B") => (2, &digits[2..]),
+        _ => (10, digits.as_str()),
+    };
+
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(AssembleError::Lexical(format!("invalid constant `{}`", buffer)));
+    }
+
+    let value = u32::from_str_radix(digits, radix).map_err(|_| AssembleError::ConstantOutOfRange(String::from(buffer)))?;
+    Ok((value, radix != 10 || buffer.contains('_')))
+}
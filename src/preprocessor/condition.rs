@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use crate::parser::tokenizer::parse_literal;
+
+// Binary operators from the loosest binding to the tightest, as in C.
+const PRECEDENCE: [&[&str]; 10] = [
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+// Longest first, so `<<` is not read as two `<`.
+const OPERATORS: [&str; 21] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+    "+", "-", "*", "/", "%", "&", "|", "^", "!", "~", "<", ">", "(",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Number(i64),
+    Name(String),
+    Operator(&'static str),
+    Close,
+}
+
+// Evaluates the condition of `.if`: integer constants, names given with
+// `-D` or `.equ`, `defined(NAME)`, parentheses and the C operators. Zero is
+// false, anything else true.
+pub fn evaluate(expression: &str, defines: &HashMap<String, i64>) -> Result<i64, String> {
+    let terms = terms(expression)?;
+    let mut evaluator = Evaluator { terms, position: 0, defines };
+    let value = evaluator.binary(0)?;
+
+    match evaluator.terms.get(evaluator.position) {
+        None => Ok(value),
+        Some(_) => Err(format!("unexpected `{}` in condition", evaluator.rest())),
+    }
+}
+
+struct Evaluator<'a> {
+    terms: Vec<Term>,
+    position: usize,
+    defines: &'a HashMap<String, i64>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn binary(&mut self, level: usize) -> Result<i64, String> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+
+        let mut value = self.binary(level + 1)?;
+        while let Some(Term::Operator(operator)) = self.terms.get(self.position).cloned() {
+            if !PRECEDENCE[level].contains(&operator) {
+                break;
+            }
+            self.position += 1;
+            let right = self.binary(level + 1)?;
+            value = apply(operator, value, right)?;
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        let term = self.terms.get(self.position).cloned().ok_or_else(|| String::from("expected a value in condition"))?;
+        self.position += 1;
+
+        match term {
+            Term::Number(value) => Ok(value),
+            Term::Operator("-") => Ok(self.unary()?.wrapping_neg()),
+            Term::Operator("!") => Ok((self.unary()? == 0) as i64),
+            Term::Operator("~") => Ok(!self.unary()?),
+            Term::Operator("(") => {
+                let value = self.binary(0)?;
+                match self.terms.get(self.position) {
+                    Some(Term::Close) => {
+                        self.position += 1;
+                        Ok(value)
+                    },
+                    _ => Err(String::from("expected `)` in condition")),
+                }
+            },
+            Term::Name(name) if name == "defined" => {
+                let parenthesized = self.terms.get(self.position) == Some(&Term::Operator("("));
+                if parenthesized {
+                    self.position += 1;
+                }
+                let defined = match self.terms.get(self.position) {
+                    Some(Term::Name(name)) => self.defines.contains_key(name),
+                    _ => return Err(String::from("expected a name after `defined`")),
+                };
+                self.position += 1;
+                if parenthesized {
+                    if self.terms.get(self.position) != Some(&Term::Close) {
+                        return Err(String::from("expected `)` after `defined(NAME`"));
+                    }
+                    self.position += 1;
+                }
+                Ok(defined as i64)
+            },
+            Term::Name(name) => self.defines.get(&name).copied()
+                .ok_or_else(|| format!("`{}` is not defined, use `-D {}=VALUE` or `defined({})`", name, name, name)),
+            _ => {
+                self.position -= 1;
+                Err(format!("unexpected `{}` in condition", self.rest()))
+            },
+        }
+    }
+
+    fn rest(&self) -> String {
+        match &self.terms[self.position] {
+            Term::Number(value) => value.to_string(),
+            Term::Name(name) => name.clone(),
+            Term::Operator(operator) => String::from(*operator),
+            Term::Close => String::from(")"),
+        }
+    }
+}
+
+fn apply(operator: &str, left: i64, right: i64) -> Result<i64, String> {
+    Ok(match operator {
+        "||" => (left != 0 || right != 0) as i64,
+        "&&" => (left != 0 && right != 0) as i64,
+        "|" => left | right,
+        "^" => left ^ right,
+        "&" => left & right,
+        "==" => (left == right) as i64,
+        "!=" => (left != right) as i64,
+        "<" => (left < right) as i64,
+        "<=" => (left <= right) as i64,
+        ">" => (left > right) as i64,
+        ">=" => (left >= right) as i64,
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => return Err(String::from("division by zero in condition")),
+        "/" => left.wrapping_div(right),
+        _ => left.wrapping_rem(right),
+    })
+}
+
+fn terms(expression: &str) -> Result<Vec<Term>, String> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut terms = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let rest: String = chars[i..].iter().collect();
+        if chars[i].is_whitespace() {
+            i += 1;
+        } else if chars[i] == ')' {
+            terms.push(Term::Close);
+            i += 1;
+        } else if let Some(operator) = OPERATORS.iter().find(|operator| rest.starts_with(*operator)) {
+            terms.push(Term::Operator(operator));
+            i += operator.len();
+        } else if chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.' || chars[i] == '$' {
+            let word: String = chars[i..].iter()
+                .take_while(|c| c.is_alphanumeric() || **c == '_' || **c == '.' || **c == '$')
+                .collect();
+            i += word.chars().count();
+            if word.starts_with(|c: char| c.is_ascii_digit()) {
+                let (value, _) = parse_literal(&word).map_err(|e| e.to_string())?;
+                terms.push(Term::Number(value as i64));
+            } else {
+                terms.push(Term::Name(word));
+            }
+        } else {
+            return Err(format!("unexpected `{}` in condition", chars[i]));
+        }
+    }
+
+    Ok(terms)
+}
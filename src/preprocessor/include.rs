@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Where `.include` reads files from; the assembler itself never touches the
+// file system, so tests and tools can serve sources from memory.
+pub trait Loader {
+    fn exists(&self, path: &Path) -> bool;
+    fn load(&self, path: &Path) -> io::Result<String>;
+
+    // Identifies a file however it is reached, for cycles and `.once`.
+    fn canonical(&self, path: &Path) -> PathBuf {
+        path.to_path_buf()
+    }
+}
+
+pub struct FileLoader;
+
+impl Loader for FileLoader {
+    fn exists(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn load(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn canonical(&self, path: &Path) -> PathBuf {
+        fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+    }
+}
+
+impl Loader for HashMap<PathBuf, String> {
+    fn exists(&self, path: &Path) -> bool {
+        self.contains_key(path)
+    }
+
+    fn load(&self, path: &Path) -> io::Result<String> {
+        self.get(path).cloned().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such file"))
+    }
+}
+
+// `.include "file"` looks next to the including file first, then in every
+// search path in order.
+pub fn resolve(name: &str, including: Option<&Path>, search_paths: &[PathBuf], loader: &dyn Loader) -> Result<PathBuf, Vec<PathBuf>> {
+    let directory = including.and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+    let candidates: Vec<PathBuf> = std::iter::once(directory.join(name))
+        .chain(search_paths.iter().map(|path| path.join(name)))
+        .collect();
+
+    match candidates.iter().find(|candidate| loader.exists(candidate)) {
+        Some(path) => Ok(path.clone()),
+        None => Err(candidates),
+    }
+}
+
+// The operand of `.include`, quoted or not.
+pub fn file_name(operand: &str) -> Option<&str> {
+    let operand = operand.trim();
+    let name = match operand.strip_prefix('"') {
+        Some(rest) => rest.strip_suffix('"')?,
+        None => operand,
+    };
+    if name.is_empty() || name.contains('"') {
+        return None;
+    }
+    Some(name)
+}
@@ -0,0 +1,136 @@
+use crate::parser::error::AssembleError;
+use crate::preprocessor::{code, words};
+
+// Invocations nested deeper than this are taken for runaway recursion.
+pub const MAX_DEPTH: usize = 16;
+
+// `.macro NAME param, ...` up to `.endm`. The body refers to parameters as
+// `\param`; labels it defines are renamed in every expansion so that a macro
+// can be invoked more than once.
+pub struct Macro {
+    pub name: String,
+    pub parameters: Vec<String>,
+    // Source line number and text of every body line.
+    pub body: Vec<(usize, String)>,
+    labels: Vec<String>,
+}
+
+impl Macro {
+    // Errors carry the source line they were found on.
+    pub fn parse(line: usize, header: &[String], body: Vec<(usize, String)>) -> Result<Macro, (AssembleError, usize)> {
+        let name = match header.first() {
+            Some(name) if is_identifier(name) => name.clone(),
+            Some(name) => return Err((AssembleError::Syntax(format!("invalid macro name `{}`", name)), line)),
+            None => return Err((AssembleError::Syntax(String::from("expected a macro name after `.macro`")), line)),
+        };
+
+        let parameters = header[1..].to_vec();
+        for (i, parameter) in parameters.iter().enumerate() {
+            if !is_identifier(parameter) {
+                return Err((AssembleError::Syntax(format!("invalid macro parameter `{}`", parameter)), line));
+            }
+            if parameters[..i].contains(parameter) {
+                return Err((AssembleError::Syntax(format!("macro parameter `{}` is declared twice", parameter)), line));
+            }
+        }
+
+        let mut labels = vec![];
+        let mut in_comment = false;
+        for (line, text) in &body {
+            let code = code(text, &mut in_comment);
+            let code = code.trim();
+            if words(code).first().map(String::as_str) == Some(".macro") {
+                return Err((AssembleError::Syntax(String::from("macros cannot be defined inside a macro")), *line));
+            }
+            if let Some(unknown) = references(code).into_iter().find(|reference| !parameters.contains(reference)) {
+                return Err((AssembleError::Syntax(format!("macro `{}` has no parameter `{}`", name, unknown)), *line));
+            }
+            if code.starts_with('(') && code.ends_with(')') {
+                labels.push(String::from(&code[1..code.len() - 1]));
+            }
+        }
+
+        Ok(Macro { name, parameters, body, labels })
+    }
+
+    // The body with the arguments substituted and its labels suffixed with
+    // the macro name and the expansion number.
+    pub fn instantiate(&self, arguments: &[String], expansion: usize) -> Vec<(usize, String)> {
+        self.body.iter()
+            .map(|(line, text)| (*line, self.substitute(text, arguments, expansion)))
+            .collect()
+    }
+
+    fn substitute(&self, text: &str, arguments: &[String], expansion: usize) -> String {
+        pieces(text).into_iter()
+            .map(|piece| match piece {
+                Piece::Parameter(name) => match self.parameters.iter().position(|parameter| *parameter == name) {
+                    Some(index) => arguments[index].clone(),
+                    None => format!("\\{}", name),
+                },
+                Piece::Word(word) if self.labels.contains(&word) => format!("{}${}${}", word, self.name, expansion),
+                Piece::Word(text) | Piece::Text(text) => text,
+            })
+            .collect()
+    }
+}
+
+enum Piece {
+    // `\name`
+    Parameter(String),
+    Word(String),
+    // Anything else, character constants included so their escapes are not
+    // taken for parameters.
+    Text(String),
+}
+
+fn pieces(text: &str) -> Vec<Piece> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut pieces = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        if chars[i] == '\'' {
+            i += 1;
+            while i < chars.len() && chars[i] != '\'' {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i = (i + 1).min(chars.len());
+            pieces.push(Piece::Text(chars[start..i].iter().collect()));
+            continue;
+        }
+
+        let word_start = if chars[i] == '\\' { i + 1 } else { i };
+        let end = (word_start..chars.len()).find(|&j| !is_identifier_char(chars[j])).unwrap_or(chars.len());
+        if end == word_start {
+            pieces.push(Piece::Text(chars[i].to_string()));
+            i += 1;
+            continue;
+        }
+
+        let word = chars[word_start..end].iter().collect();
+        pieces.push(if word_start > start { Piece::Parameter(word) } else { Piece::Word(word) });
+        i = end;
+    }
+
+    pieces
+}
+
+// Every `\param` the code refers to.
+fn references(code: &str) -> Vec<String> {
+    pieces(code).into_iter()
+        .filter_map(|piece| match piece {
+            Piece::Parameter(name) => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_identifier(word: &str) -> bool {
+    word.chars().all(is_identifier_char) && !word.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.' || c == '$'
+}
@@ -0,0 +1,437 @@
+pub mod macros;
+pub mod include;
+pub mod condition;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use crate::parser::compiler::CompileOptions;
+use crate::parser::error::{AssembleError, Diagnostic, Diagnostics};
+use crate::parser::span::Span;
+use crate::preprocessor::condition::evaluate;
+use crate::preprocessor::include::{file_name, resolve, Loader};
+use crate::preprocessor::macros::{Macro, MAX_DEPTH};
+
+// The source with every file included and every macro expanded, and where
+// each of its lines came from so diagnostics and listings can point back at
+// the original files.
+pub struct Expansion {
+    pub text: String,
+    pub origins: Vec<Origin>,
+    // The file being assembled first, then every included file.
+    pub files: Vec<SourceFile>,
+}
+
+pub struct SourceFile {
+    pub name: String,
+    pub source: String,
+    // File index and line of the `.include` that pulled the file in.
+    pub included_from: Option<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Origin {
+    pub file: usize,
+    pub line: usize,
+    // The outermost macro call the line was expanded from, if any.
+    pub call: Option<Span>,
+    // Every macro expansion the line went through, innermost first.
+    pub trace: Vec<Frame>,
+}
+
+// A line of a macro body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub name: String,
+    pub file: usize,
+    pub line: usize,
+}
+
+impl Expansion {
+    pub fn origin(&self, span: Span) -> Origin {
+        match self.origins.get(span.line.wrapping_sub(1)) {
+            Some(origin) => origin.clone(),
+            // Past the end of the text, which is the end of the main file.
+            None => Origin { file: 0, line: self.files[0].source.split('\n').count() + 1, call: None, trace: vec![] },
+        }
+    }
+
+    pub fn remap_span(&self, span: Span) -> Span {
+        match self.origin(span) {
+            Origin { call: Some(call), .. } => call,
+            origin => Span::new(origin.line, span.column, span.len),
+        }
+    }
+
+    pub fn remap(&self, diagnostic: Diagnostic) -> Diagnostic {
+        let origin = self.origin(diagnostic.span);
+        let span = self.remap_span(diagnostic.span);
+        let diagnostic = Diagnostic { span, file: self.file_name(origin.file), ..diagnostic };
+
+        origin.trace.iter().fold(diagnostic, |diagnostic, frame| diagnostic.with_note(self.frame_note(frame)))
+    }
+
+    // `None` for the file being assembled, as in `Diagnostic::file`.
+    fn file_name(&self, file: usize) -> Option<String> {
+        if file == 0 { None } else { Some(self.files[file].name.clone()) }
+    }
+
+    fn frame_note(&self, frame: &Frame) -> String {
+        match self.file_name(frame.file) {
+            Some(name) => format!("note: expanded from macro `{}` at {}:{}", frame.name, name, frame.line),
+            None => format!("note: expanded from macro `{}` at line {}", frame.name, frame.line),
+        }
+    }
+}
+
+pub fn preprocess(source: &str, options: &CompileOptions, loader: &dyn Loader, diagnostics: &mut Diagnostics) -> Expansion {
+    let name = options.path.as_ref().map(|path| path.display().to_string()).unwrap_or_default();
+    let mut preprocessor = Preprocessor {
+        expansion: Expansion {
+            text: String::new(),
+            origins: vec![],
+            files: vec![SourceFile { name, source: String::from(source), included_from: None }],
+        },
+        lines: vec![],
+        paths: vec![options.path.clone()],
+        including: options.path.iter().map(|path| loader.canonical(path)).collect(),
+        once: vec![],
+        macros: HashMap::new(),
+        expansions: 0,
+        conditions: vec![],
+        defines: options.defines.iter().cloned().collect(),
+        options,
+        loader,
+    };
+
+    preprocessor.process(0, diagnostics);
+    let mut expansion = preprocessor.expansion;
+    expansion.text = preprocessor.lines.join("\n");
+    expansion
+}
+
+struct Preprocessor<'a> {
+    expansion: Expansion,
+    lines: Vec<String>,
+    // Path of every file by index, `None` for source that is not a file.
+    paths: Vec<Option<PathBuf>>,
+    // The files being included, outermost first, to catch cycles.
+    including: Vec<PathBuf>,
+    // Files marked `.once` that were already included.
+    once: Vec<PathBuf>,
+    // Every macro with the file and line it was defined on.
+    macros: HashMap<String, (Macro, usize, usize)>,
+    // Numbers every expansion, so labels inside macro bodies stay unique.
+    expansions: usize,
+    // The open `.if`s, outermost first.
+    conditions: Vec<Condition>,
+    // Names `.if` can test: `-D` defines and the `.equ` constants seen so far.
+    defines: HashMap<String, i64>,
+    options: &'a CompileOptions,
+    loader: &'a dyn Loader,
+}
+
+struct Condition {
+    // Whether the lines of the current branch are assembled.
+    active: bool,
+    // Whether a branch was taken already, so `.else` must skip.
+    taken: bool,
+    in_else: bool,
+    file: usize,
+    span: Span,
+}
+
+impl<'a> Preprocessor<'a> {
+    fn process(&mut self, file: usize, diagnostics: &mut Diagnostics) {
+        let source = self.expansion.files[file].source.clone();
+        let lines: Vec<&str> = source.split('\n').collect();
+        let mut in_comment = false;
+        let mut i = 0;
+        let base = self.conditions.len();
+
+        while i < lines.len() {
+            let line = i + 1;
+            let span = line_span(line, lines[i]);
+            let code = code(lines[i], &mut in_comment);
+            let words = words(&code);
+            i += 1;
+
+            match words.first().map(String::as_str) {
+                Some(directive) if is_conditional(directive) => {
+                    if let Err(error) = self.conditional(directive, operand(&code, directive), file, span, base) {
+                        diagnostics.push(self.error(file, error, span));
+                    }
+                },
+                _ if !self.active() => {},
+                Some(".macro") => {
+                    let body_start = i;
+                    while i < lines.len() && words_of(lines[i], &mut in_comment).first().map(String::as_str) != Some(".endm") {
+                        i += 1;
+                    }
+                    if i == lines.len() {
+                        let name = words.get(1).cloned().unwrap_or_default();
+                        let message = format!("unterminated macro `{}`, expected `.endm`", name);
+                        diagnostics.push(self.error(file, AssembleError::Syntax(message), span));
+                        break;
+                    }
+
+                    let body = (body_start..i).map(|index| (index + 1, String::from(lines[index]))).collect();
+                    i += 1;
+                    match Macro::parse(line, &words[1..], body) {
+                        Ok(definition) => self.define(definition, file, span, diagnostics),
+                        Err((error, line)) => diagnostics.push(self.error(file, error, line_span(line, lines[line - 1]))),
+                    }
+                },
+                Some(".endm") => {
+                    let error = AssembleError::Syntax(String::from("`.endm` without `.macro`"));
+                    diagnostics.push(self.error(file, error, span));
+                },
+                Some(".include") => {
+                    if let Err(diagnostic) = self.include(file, code.trim()[".include".len()..].trim(), span, diagnostics) {
+                        diagnostics.push(diagnostic);
+                    }
+                },
+                Some(".once") => {
+                    if let Some(path) = &self.paths[file] {
+                        self.once.push(self.loader.canonical(path));
+                    }
+                },
+                Some(name) if self.macros.contains_key(name) => {
+                    if let Err(diagnostic) = self.expand(name, &words[1..], file, span, &[]) {
+                        diagnostics.push(diagnostic);
+                    }
+                },
+                _ => {
+                    self.record_constant(&words);
+                    self.emit(lines[line - 1], Origin { file, line, call: None, trace: vec![] });
+                },
+            }
+        }
+
+        for condition in self.conditions.drain(base..).collect::<Vec<_>>() {
+            let error = AssembleError::Syntax(String::from("unterminated `.if`, expected `.endif`"));
+            diagnostics.push(self.error(condition.file, error, condition.span));
+        }
+    }
+
+    // `.if`, `.ifdef`, `.ifndef`, `.else` and `.endif`. Conditions opened
+    // before `base`, in another file or macro, cannot be closed here.
+    fn conditional(&mut self, directive: &str, operand: &str, file: usize, span: Span, base: usize) -> Result<(), AssembleError> {
+        let open = self.conditions.len() > base;
+        match directive {
+            ".else" | ".endif" if !open => Err(AssembleError::Syntax(format!("`{}` without `.if`", directive))),
+            ".else" => {
+                let condition = self.conditions.last_mut().unwrap();
+                if condition.in_else {
+                    return Err(AssembleError::Syntax(String::from("`.else` after `.else`, expected `.endif`")));
+                }
+                condition.in_else = true;
+                condition.active = !condition.taken;
+                condition.taken = true;
+                Ok(())
+            },
+            ".endif" => {
+                self.conditions.pop();
+                Ok(())
+            },
+            _ => {
+                // Conditions inside a skipped branch are not evaluated at all.
+                let enclosing = self.active();
+                let value = match directive {
+                    _ if !enclosing => Ok(false),
+                    ".if" => evaluate(operand, &self.defines).map(|value| value != 0),
+                    _ if words(operand).len() != 1 => Err(format!("expected a name after `{}`", directive)),
+                    ".ifdef" => Ok(self.defines.contains_key(operand)),
+                    _ => Ok(!self.defines.contains_key(operand)),
+                };
+
+                // A condition in error still needs its `.endif`.
+                let active = *value.as_ref().unwrap_or(&false);
+                self.conditions.push(Condition { active, taken: active || !enclosing, in_else: false, file, span });
+                value.map(|_| ()).map_err(AssembleError::Syntax)
+            },
+        }
+    }
+
+    fn active(&self) -> bool {
+        self.conditions.iter().all(|condition| condition.active)
+    }
+
+    // Makes `.equ` constants visible to later conditions.
+    fn record_constant(&mut self, words: &[String]) {
+        if let [directive, name, value] = words {
+            if directive == ".equ" || directive == ".define" {
+                if let Ok(value) = evaluate(value, &HashMap::new()) {
+                    self.defines.insert(name.clone(), value);
+                }
+            }
+        }
+    }
+
+    fn include(&mut self, file: usize, operand: &str, span: Span, diagnostics: &mut Diagnostics) -> Result<(), Diagnostic> {
+        let name = file_name(operand).ok_or_else(|| {
+            self.error(file, AssembleError::Syntax(String::from("expected a file name after `.include`")), span)
+        })?;
+        let path = resolve(name, self.paths[file].as_deref(), &self.options.include_paths, self.loader).map_err(|searched| {
+            let searched: Vec<String> = searched.iter().map(|path| path.display().to_string()).collect();
+            self.error(file, AssembleError::Include(format!("cannot find `{}` to include", name)), span)
+                .with_note(format!("note: looked for {}", searched.join(", ")))
+        })?;
+
+        let canonical = self.loader.canonical(&path);
+        if self.once.contains(&canonical) {
+            return Ok(());
+        }
+        if let Some(position) = self.including.iter().position(|including| *including == canonical) {
+            let cycle: Vec<String> = self.including[position..].iter().chain(std::iter::once(&canonical))
+                .map(|path| path.display().to_string())
+                .collect();
+            return Err(self.error(file, AssembleError::Include(format!("`{}` includes itself", path.display())), span)
+                .with_note(format!("note: include cycle {}", cycle.join(" -> ")))
+                .with_note(String::from("help: mark shared files with `.once`")));
+        }
+
+        let source = self.loader.load(&path).map_err(|e| {
+            self.error(file, AssembleError::Include(format!("cannot read `{}`: {}", path.display(), e)), span)
+        })?;
+        self.expansion.files.push(SourceFile { name: path.display().to_string(), source, included_from: Some((file, span.line)) });
+        self.paths.push(Some(path));
+        self.including.push(canonical);
+        self.process(self.expansion.files.len() - 1, diagnostics);
+        self.including.pop();
+
+        Ok(())
+    }
+
+    fn define(&mut self, definition: Macro, file: usize, span: Span, diagnostics: &mut Diagnostics) {
+        if let Some((_, defined_in, line)) = self.macros.get(&definition.name) {
+            let location = match self.expansion.file_name(*defined_in) {
+                Some(name) => format!("{}:{}", name, line),
+                None => format!("line {}", line),
+            };
+            let error = self.error(file, AssembleError::Redefinition(definition.name.clone()), span)
+                .with_note(format!("note: previously defined on {}", location));
+            diagnostics.push(error);
+        } else {
+            self.macros.insert(definition.name.clone(), (definition, file, span.line));
+        }
+    }
+
+    fn expand(&mut self, name: &str, arguments: &[String], file: usize, call: Span, trace: &[Frame]) -> Result<(), Diagnostic> {
+        if trace.len() >= MAX_DEPTH {
+            let message = format!("macro `{}` expands more than {} levels deep", name, MAX_DEPTH);
+            return Err(self.traced(AssembleError::Syntax(message), file, call, trace)
+                .with_note(String::from("help: a macro must not invoke itself")));
+        }
+        let (definition, defined_in, _) = &self.macros[name];
+        let (parameters, defined_in) = (definition.parameters.len(), *defined_in);
+        if arguments.len() != parameters {
+            let message = format!("macro `{}` takes {} argument(s) but {} were given", name, parameters, arguments.len());
+            return Err(self.traced(AssembleError::Syntax(message), file, call, trace));
+        }
+
+        self.expansions += 1;
+        let lines = self.macros[name].0.instantiate(arguments, self.expansions);
+        let base = self.conditions.len();
+        let mut result = Ok(());
+        let mut in_comment = false;
+        for (line, text) in lines {
+            let mut inner = vec![Frame { name: String::from(name), file: defined_in, line }];
+            inner.extend_from_slice(trace);
+
+            let code = code(&text, &mut in_comment);
+            let words = words(&code);
+            result = match words.first().map(String::as_str) {
+                Some(directive) if is_conditional(directive) => self.conditional(directive, operand(&code, directive), file, call, base)
+                    .map_err(|error| self.traced(error, file, call, &inner)),
+                _ if !self.active() => Ok(()),
+                Some(word) if self.macros.contains_key(word) => self.expand(word, &words[1..], file, call, &inner),
+                _ => {
+                    self.record_constant(&words);
+                    self.emit(&text, Origin { file, line: call.line, call: Some(call), trace: inner });
+                    Ok(())
+                },
+            };
+            if result.is_err() {
+                break;
+            }
+        }
+
+        if result.is_ok() && self.conditions.len() > base {
+            let message = format!("unterminated `.if` in macro `{}`, expected `.endif`", name);
+            result = Err(self.traced(AssembleError::Syntax(message), file, call, trace));
+        }
+        self.conditions.truncate(base);
+        result
+    }
+
+    fn traced(&self, error: AssembleError, file: usize, call: Span, trace: &[Frame]) -> Diagnostic {
+        trace.iter().fold(self.error(file, error, call), |diagnostic, frame| {
+            diagnostic.with_note(self.expansion.frame_note(frame))
+        })
+    }
+
+    fn emit(&mut self, text: &str, origin: Origin) {
+        self.lines.push(String::from(text));
+        self.expansion.origins.push(origin);
+    }
+
+    fn error(&self, file: usize, error: AssembleError, span: Span) -> Diagnostic {
+        Diagnostic { file: self.expansion.file_name(file), ..Diagnostic::new(error, span) }
+    }
+}
+
+// The line without `//`, `#` and `/* */` comments; `in_comment` carries an
+// open block comment over to the next line.
+pub fn code(line: &str, in_comment: &mut bool) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut code = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let next = chars.get(i + 1).copied();
+        if *in_comment {
+            if chars[i] == '*' && next == Some('/') {
+                *in_comment = false;
+                i += 1;
+            }
+        } else if chars[i] == '/' && next == Some('*') {
+            *in_comment = true;
+            i += 1;
+        } else if chars[i] == '#' || (chars[i] == '/' && next == Some('/')) {
+            break;
+        } else {
+            code.push(chars[i]);
+        }
+        i += 1;
+    }
+
+    code
+}
+
+// Directive and macro operands, separated by whitespace or commas.
+pub fn words(code: &str) -> Vec<String> {
+    code.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn is_conditional(directive: &str) -> bool {
+    matches!(directive, ".if" | ".ifdef" | ".ifndef" | ".else" | ".endif")
+}
+
+// What follows the directive on its line.
+fn operand<'a>(code: &'a str, directive: &str) -> &'a str {
+    code.trim().get(directive.len()..).unwrap_or("").trim()
+}
+
+fn words_of(line: &str, in_comment: &mut bool) -> Vec<String> {
+    words(&code(line, in_comment))
+}
+
+// The trimmed text of a line, so a whole call or directive gets underlined.
+fn line_span(line: usize, text: &str) -> Span {
+    let column = text.chars().take_while(|c| c.is_whitespace()).count() + 1;
+    let trimmed = code(text, &mut false);
+    Span::new(line, column, trimmed.trim().chars().count())
+}
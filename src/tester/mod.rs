@@ -0,0 +1,168 @@
+pub mod script;
+
+use crate::disassembler::read_words;
+use crate::emulator::{Cpu, RAM_SIZE};
+use crate::parser::compiler::compile;
+use crate::parser::span::Spanned;
+use crate::tester::script::{Column, Command, Variable};
+
+// Gives the runner access to the files a script names (`load`, `compare-to`),
+// resolved relative to the script.
+pub trait Files {
+    fn read(&mut self, name: &str) -> Result<String, String>;
+}
+
+#[derive(Debug, Default)]
+pub struct TestRun {
+    pub output: String,
+    pub output_file: Option<String>,
+    pub compare_to: Option<String>,
+    pub echoes: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+    pub line: usize,
+    pub expected: String,
+    pub actual: String,
+}
+
+struct Runner<'a> {
+    files: &'a mut dyn Files,
+    cpu: Cpu,
+    columns: Vec<Column>,
+    run: TestRun,
+}
+
+pub fn run_script(commands: &[Spanned<Command>], files: &mut dyn Files) -> Result<TestRun, String> {
+    let mut runner = Runner {
+        files,
+        cpu: Cpu::new(),
+        columns: vec![],
+        run: TestRun::default(),
+    };
+    runner.execute(commands)?;
+    Ok(runner.run)
+}
+
+// Compares the produced output with a `.cmp` table line by line; a `*` in the
+// expected text matches any character.
+pub fn compare(output: &str, expected: &str) -> Result<(), Mismatch> {
+    let actual_lines: Vec<&str> = output.lines().map(|line| line.trim_end_matches('\r')).collect();
+    let expected_lines: Vec<&str> = expected.lines().map(|line| line.trim_end_matches('\r')).collect();
+
+    for index in 0..actual_lines.len().max(expected_lines.len()) {
+        let actual = actual_lines.get(index).copied().unwrap_or("");
+        let wanted = expected_lines.get(index).copied().unwrap_or("");
+        let matches = actual.chars().count() == wanted.chars().count()
+            && actual.chars().zip(wanted.chars()).all(|(a, e)| e == '*' || a == e);
+
+        if !matches {
+            return Err(Mismatch { line: index + 1, expected: String::from(wanted), actual: String::from(actual) });
+        }
+    }
+
+    Ok(())
+}
+
+impl<'a> Runner<'a> {
+    fn execute(&mut self, commands: &[Spanned<Command>]) -> Result<(), String> {
+        for command in commands {
+            self.execute_one(&command.node).map_err(|message| format!("{}: {}", command.span, message))?;
+        }
+        Ok(())
+    }
+
+    fn execute_one(&mut self, command: &Command) -> Result<(), String> {
+        match command {
+            Command::Load(name) => {
+                let source = self.files.read(name)?;
+                let hack = if name.ends_with(".hack") {
+                    source
+                } else {
+                    compile(source).map_err(|diagnostics| format!("cannot assemble `{}`: {}", name, diagnostics[0]))?
+                };
+                let program = read_words(&hack).map_err(|diagnostics| format!("cannot load `{}`: {}", name, diagnostics[0]))?;
+                self.cpu.load(&program)?;
+            },
+            Command::OutputFile(name) => self.run.output_file = Some(name.clone()),
+            Command::CompareTo(name) => self.run.compare_to = Some(self.files.read(name)?),
+            Command::OutputList(columns) => {
+                self.columns = columns.clone();
+                let header: Vec<String> = self.columns.iter().map(header_cell).collect();
+                self.write_row(header);
+            },
+            Command::Set(variable, value) => match variable {
+                Variable::Ram(address) if *address < RAM_SIZE => self.cpu.ram_mut()[*address] = *value,
+                Variable::Ram(address) => return Err(format!("RAM address {} is out of range", address)),
+                Variable::Pc => self.cpu.pc = *value,
+                Variable::A => self.cpu.a = *value,
+                Variable::D => self.cpu.d = *value,
+                Variable::Time => return Err(String::from("`time` cannot be set")),
+            },
+            Command::Tick => {},
+            Command::Tock | Command::TickTock => self.cpu.step(),
+            Command::Output => {
+                let row: Vec<String> = self.columns.iter().map(|column| self.value_cell(column)).collect();
+                self.write_row(row);
+            },
+            Command::Echo(text) => self.run.echoes.push(text.clone()),
+            Command::ClearEcho => self.run.echoes.clear(),
+            Command::Repeat(count, body) => {
+                for _ in 0..*count {
+                    self.execute(body)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn write_row(&mut self, cells: Vec<String>) {
+        self.run.output.push('|');
+        for cell in cells {
+            self.run.output.push_str(&cell);
+            self.run.output.push('|');
+        }
+        self.run.output.push('\n');
+    }
+
+    fn value_cell(&self, column: &Column) -> String {
+        let value = match column.variable {
+            Variable::Ram(address) => self.cpu.ram().get(address).copied().unwrap_or(0),
+            Variable::Pc => self.cpu.pc,
+            Variable::A => self.cpu.a,
+            Variable::D => self.cpu.d,
+            Variable::Time => self.cpu.cycles() as u16,
+        };
+
+        let text = match column.format {
+            'X' => last_chars(&format!("{:04X}", value), column.width, '0'),
+            'B' => last_chars(&format!("{:016b}", value), column.width, '0'),
+            'S' => format!("{:<width$}", value, width = column.width),
+            _ => format!("{:>width$}", value as i16, width = column.width),
+        };
+
+        format!("{}{}{}", " ".repeat(column.pad_left), text, " ".repeat(column.pad_right))
+    }
+}
+
+// Names are centered over the whole cell, truncated if they do not fit.
+fn header_cell(column: &Column) -> String {
+    let width = column.pad_left + column.width + column.pad_right;
+    let name: String = column.name.chars().take(width).collect();
+    let left = (width - name.chars().count()) / 2;
+    let right = width - name.chars().count() - left;
+
+    format!("{}{}{}", " ".repeat(left), name, " ".repeat(right))
+}
+
+fn last_chars(text: &str, width: usize, fill: char) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() >= width {
+        chars[chars.len() - width..].iter().collect()
+    } else {
+        let mut padded: String = std::iter::repeat_n(fill, width - chars.len()).collect();
+        padded.push_str(text);
+        padded
+    }
+}
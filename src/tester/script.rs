@@ -0,0 +1,231 @@
+use crate::parser::error::{AssembleError, Diagnostic};
+use crate::parser::span::{Span, Spanned};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Variable {
+    Ram(usize),
+    Pc,
+    A,
+    D,
+    Time,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub variable: Variable,
+    pub name: String,
+    pub format: char,
+    pub pad_left: usize,
+    pub width: usize,
+    pub pad_right: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Load(String),
+    OutputFile(String),
+    CompareTo(String),
+    OutputList(Vec<Column>),
+    Set(Variable, u16),
+    Tick,
+    Tock,
+    TickTock,
+    Output,
+    Echo(String),
+    ClearEcho,
+    Repeat(u32, Vec<Spanned<Command>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Word {
+    Text(String),
+    Quoted(String),
+    Terminator,
+    OpenBlock,
+    CloseBlock,
+}
+
+pub fn parse_script(script: &str) -> Result<Vec<Spanned<Command>>, Diagnostic> {
+    let words = split_words(script)?;
+    let mut index = 0;
+    let commands = parse_block(&words, &mut index, None)?;
+    Ok(commands)
+}
+
+// `block` is the span of the enclosing `repeat`, if any.
+fn parse_block(words: &[Spanned<Word>], index: &mut usize, block: Option<Span>) -> Result<Vec<Spanned<Command>>, Diagnostic> {
+    let mut commands = vec![];
+
+    while *index < words.len() {
+        let word = &words[*index];
+        let span = word.span;
+        *index += 1;
+
+        let name = match &word.node {
+            Word::Terminator => continue,
+            Word::CloseBlock if block.is_some() => return Ok(commands),
+            Word::Text(name) => name.clone(),
+            _ => return Err(syntax_error("expected a command", span)),
+        };
+
+        let mut arguments: Vec<Spanned<String>> = vec![];
+        while let Some(Spanned { node: Word::Text(text), span }) | Some(Spanned { node: Word::Quoted(text), span }) = words.get(*index) {
+            arguments.push(Spanned::new(text.clone(), *span));
+            *index += 1;
+        }
+
+        let command = match (name.as_str(), arguments.len()) {
+            ("repeat", 1) => {
+                let count = arguments[0].node.parse()
+                    .map_err(|_| syntax_error("expected a repeat count", arguments[0].span))?;
+                match words.get(*index) {
+                    Some(Spanned { node: Word::OpenBlock, .. }) => *index += 1,
+                    _ => return Err(syntax_error("expected `{` after `repeat`", span)),
+                }
+                Command::Repeat(count, parse_block(words, index, Some(span))?)
+            },
+            ("load", 1) => Command::Load(arguments[0].node.clone()),
+            ("output-file", 1) => Command::OutputFile(arguments[0].node.clone()),
+            ("compare-to", 1) => Command::CompareTo(arguments[0].node.clone()),
+            ("output-list", _) => Command::OutputList(arguments.iter().map(parse_column).collect::<Result<_, _>>()?),
+            ("set", 2) => Command::Set(parse_variable(&arguments[0])?, parse_value(&arguments[1])?),
+            ("tick", 0) => Command::Tick,
+            ("tock", 0) => Command::Tock,
+            ("ticktock", 0) => Command::TickTock,
+            ("output", 0) => Command::Output,
+            ("echo", 1) => Command::Echo(arguments[0].node.clone()),
+            ("clear-echo", 0) => Command::ClearEcho,
+            ("load", _) | ("output-file", _) | ("compare-to", _) | ("set", _) | ("tick", _) | ("tock", _)
+            | ("ticktock", _) | ("output", _) | ("echo", _) | ("clear-echo", _) | ("repeat", _) => {
+                return Err(syntax_error(&format!("wrong number of arguments for `{}`", name), span));
+            },
+            _ => return Err(syntax_error(&format!("unknown command `{}`", name), span)),
+        };
+
+        commands.push(Spanned::new(command, span));
+    }
+
+    if let Some(span) = block {
+        return Err(syntax_error("unterminated `repeat` block, expected `}`", span));
+    }
+    Ok(commands)
+}
+
+// `RAM[0]%D2.6.2` prints RAM[0] in decimal, two spaces either side of a
+// six character field.
+fn parse_column(argument: &Spanned<String>) -> Result<Column, Diagnostic> {
+    let text = argument.node.as_str();
+    let (name, format) = match text.find('%') {
+        Some(index) => (&text[..index], &text[index + 1..]),
+        None => (text, "D1.6.1"),
+    };
+
+    let mut chars = format.chars();
+    let kind = chars.next().filter(|c| "DXBS".contains(*c))
+        .ok_or_else(|| syntax_error("expected an output format `%D`, `%X`, `%B` or `%S`", argument.span))?;
+    let sizes: Vec<usize> = chars.as_str().split('.')
+        .map(|size| size.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| syntax_error("expected an output format like `%D2.6.2`", argument.span))?;
+    if sizes.len() != 3 {
+        return Err(syntax_error("expected an output format like `%D2.6.2`", argument.span));
+    }
+
+    let variable = parse_variable(&Spanned::new(String::from(name), argument.span))?;
+    Ok(Column {
+        variable,
+        name: String::from(name),
+        format: kind,
+        pad_left: sizes[0],
+        width: sizes[1],
+        pad_right: sizes[2],
+    })
+}
+
+fn parse_variable(argument: &Spanned<String>) -> Result<Variable, Diagnostic> {
+    let text = argument.node.as_str();
+    match text {
+        "PC" => Ok(Variable::Pc),
+        "A" => Ok(Variable::A),
+        "D" => Ok(Variable::D),
+        "time" => Ok(Variable::Time),
+        _ if text.starts_with("RAM[") && text.ends_with(']') => text[4..text.len() - 1].parse()
+            .map(Variable::Ram)
+            .map_err(|_| syntax_error(&format!("invalid RAM address in `{}`", text), argument.span)),
+        _ => Err(syntax_error(&format!("unknown variable `{}`", text), argument.span)),
+    }
+}
+
+// Values are decimal unless prefixed with `%X`, `%B` or `%D`.
+fn parse_value(argument: &Spanned<String>) -> Result<u16, Diagnostic> {
+    let text = argument.node.as_str();
+    let parsed = match text.get(..2) {
+        Some("%X") => i32::from_str_radix(&text[2..], 16),
+        Some("%B") => i32::from_str_radix(&text[2..], 2),
+        Some("%D") => text[2..].parse(),
+        _ => text.parse(),
+    };
+
+    parsed.ok()
+        .filter(|value| (-32768..=65535).contains(value))
+        .map(|value| value as u16)
+        .ok_or_else(|| Diagnostic::new(AssembleError::ConstantOutOfRange(String::from(text)), argument.span))
+}
+
+fn syntax_error(message: &str, span: Span) -> Diagnostic {
+    Diagnostic::new(AssembleError::Syntax(String::from(message)), span)
+}
+
+fn split_words(script: &str) -> Result<Vec<Spanned<Word>>, Diagnostic> {
+    let chars: Vec<char> = script.chars().collect();
+    let mut words = vec![];
+    let (mut line, mut column) = (1, 1);
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = Span::new(line, column, 1);
+        let c = chars[i];
+        let rest = &chars[i..];
+
+        let consumed = if c.is_whitespace() {
+            1
+        } else if rest.starts_with(&['/', '/']) {
+            rest.iter().take_while(|c| **c != '\n').count()
+        } else if rest.starts_with(&['/', '*']) {
+            let end = rest.windows(2).position(|pair| pair == ['*', '/'])
+                .ok_or_else(|| syntax_error("unterminated comment", start))?;
+            end + 2
+        } else if c == '"' {
+            let end = rest[1..].iter().position(|c| *c == '"' || *c == '\n')
+                .filter(|end| rest[end + 1] == '"')
+                .ok_or_else(|| syntax_error("unterminated string", start))?;
+            words.push(Spanned::new(Word::Quoted(rest[1..end + 1].iter().collect()), start));
+            end + 2
+        } else if ",;!".contains(c) {
+            words.push(Spanned::new(Word::Terminator, start));
+            1
+        } else if c == '{' || c == '}' {
+            words.push(Spanned::new(if c == '{' { Word::OpenBlock } else { Word::CloseBlock }, start));
+            1
+        } else {
+            let len = rest.iter()
+                .take_while(|c| !c.is_whitespace() && !",;!{}\"".contains(**c))
+                .count();
+            let text: String = rest[..len].iter().collect();
+            words.push(Spanned::new(Word::Text(text), Span::new(line, column, len)));
+            len
+        };
+
+        for c in &chars[i..i + consumed] {
+            if *c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+        i += consumed;
+    }
+
+    Ok(words)
+}
@@ -0,0 +1,241 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use crate::cli::{run, Console, EXIT_SUCCESS, EXIT_FAILURE, EXIT_USAGE};
+use super::fixtures::*;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("assembler_rust_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn run_cli(args: &[&str], stdin: &str) -> (i32, String, String) {
+    let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+    let mut stdin = stdin.as_bytes();
+    let mut stdout: Vec<u8> = vec![];
+    let mut stderr: Vec<u8> = vec![];
+    let status = run(&args, &mut Console { stdin: &mut stdin, stdout: &mut stdout, stderr: &mut stderr });
+    (status, String::from_utf8(stdout).unwrap(), String::from_utf8(stderr).unwrap())
+}
+
+#[test]
+fn test_cli_writes_sibling_hack_files() {
+    let dir = scratch_dir("sibling");
+    fs::write(dir.join("Add.asm"), ADD_ASM).unwrap();
+    fs::write(dir.join("Max.asm"), MAX_ASM).unwrap();
+
+    let add = dir.join("Add.asm");
+    let max = dir.join("Max.asm");
+    let (status, _, _) = run_cli(&[add.to_str().unwrap(), max.to_str().unwrap()], "");
+
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(fs::read_to_string(dir.join("Add.hack")).unwrap(), format!("{}\n", ADD_HACK));
+    assert_eq!(fs::read_to_string(dir.join("Max.hack")).unwrap(), format!("{}\n", MAX_HACK));
+}
+
+#[test]
+fn test_cli_reads_stdin_and_writes_stdout() {
+    let (status, stdout, _) = run_cli(&["-"], ADD_ASM);
+
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, format!("{}\n", ADD_HACK));
+}
+
+#[test]
+fn test_cli_reports_missing_input() {
+    let dir = scratch_dir("missing");
+    let missing = dir.join("Missing.asm");
+    let (status, _, stderr) = run_cli(&[missing.to_str().unwrap()], "");
+
+    assert_ne!(status, EXIT_SUCCESS);
+    assert!(stderr.contains("cannot read"));
+}
+
+#[test]
+fn test_cli_rejects_output_with_multiple_inputs() {
+    let (status, _, stderr) = run_cli(&["-o", "out.hack", "a.asm", "b.asm"], "");
+
+    assert_eq!(status, EXIT_USAGE);
+    assert!(stderr.contains("multiple inputs"));
+}
+
+#[test]
+fn test_cli_fails_on_invalid_source() {
+    let (status, stdout, stderr) = run_cli(&["-"], "D=D+X\n");
+
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(stdout.is_empty());
+    assert!(stderr.contains("unknown comp mnemonic `D+X`"));
+}
+
+#[test]
+fn test_cli_reports_all_errors_and_writes_nothing() {
+    let dir = scratch_dir("broken");
+    let broken = dir.join("Broken.asm");
+    fs::write(&broken, "D=X\n@1\nD=Y\n").unwrap();
+
+    let (status, _, stderr) = run_cli(&[broken.to_str().unwrap()], "");
+
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(stderr.contains("Broken.asm:1:3"));
+    assert!(stderr.contains("Broken.asm:3:3"));
+    assert!(!dir.join("Broken.hack").exists());
+}
+
+#[test]
+fn test_cli_disassembles_to_stdout() {
+    let (status, stdout, _) = run_cli(&["disasm", "-"], ADD_HACK);
+
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, "@2\nD=A\n@3\nD=D+A\n@R0\nM=D\n");
+}
+
+#[test]
+fn test_cli_runs_program_and_prints_ram() {
+    let (status, stdout, _) = run_cli(&["run", "--set", "0=3", "--set", "1=7", "--ram", "0..3", "-"], MAX_ASM);
+
+    assert_eq!(status, EXIT_SUCCESS);
+    assert!(stdout.starts_with("halted after"));
+    assert!(stdout.ends_with("RAM[0] = 3\nRAM[1] = 7\nRAM[2] = 7\n"));
+}
+
+#[test]
+fn test_cli_runs_test_scripts() {
+    let dir = scratch_dir("script");
+    fs::write(dir.join("Max.asm"), MAX_ASM).unwrap();
+    fs::write(dir.join("Max.tst"), MAX_TST).unwrap();
+    fs::write(dir.join("Max.cmp"), MAX_CMP).unwrap();
+
+    let script = dir.join("Max.tst");
+    let (status, stdout, _) = run_cli(&["test", script.to_str().unwrap()], "");
+
+    assert_eq!(status, EXIT_SUCCESS);
+    assert!(stdout.contains("comparison ended successfully"));
+    assert_eq!(fs::read_to_string(dir.join("Max.out")).unwrap(), MAX_CMP);
+
+    fs::write(dir.join("Max.cmp"), MAX_CMP.replace("47  |\n", "48  |\n")).unwrap();
+    let (status, _, stderr) = run_cli(&["test", script.to_str().unwrap()], "");
+
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(stderr.contains("comparison failure at line 3"));
+}
+
+#[test]
+fn test_cli_writes_listing_next_to_output() {
+    let dir = scratch_dir("listing");
+    let input = dir.join("Add.asm");
+    let output = dir.join("out").with_extension("hack");
+    fs::write(&input, ADD_ASM).unwrap();
+
+    let (status, _, _) = run_cli(&["--listing", "-o", output.to_str().unwrap(), input.to_str().unwrap()], "");
+    assert_eq!(status, EXIT_SUCCESS);
+    assert!(fs::read_to_string(dir.join("out.lst")).unwrap().starts_with("  ROM  HEX"));
+
+    let (status, _, stderr) = run_cli(&["--listing", "-"], ADD_ASM);
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(stderr.contains("`.lst`"));
+}
+
+#[test]
+fn test_cli_writes_other_formats() {
+    let dir = scratch_dir("format");
+    let input = dir.join("Add.asm");
+    fs::write(&input, ADD_ASM).unwrap();
+
+    let (status, _, _) = run_cli(&["-f", "bin", input.to_str().unwrap()], "");
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(fs::read(dir.join("Add.bin")).unwrap().len(), 2 * ADD_HACK.lines().count());
+
+    let (status, _, stderr) = run_cli(&["--format", "elf", "-"], ADD_ASM);
+    assert_eq!(status, EXIT_USAGE);
+    assert!(stderr.contains("unknown output format `elf`"));
+}
+
+#[test]
+fn test_cli_rejects_programs_larger_than_rom_size() {
+    let (status, _, stderr) = run_cli(&["-f", "readmemh", "--rom-size", "2", "-"], ADD_ASM);
+
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(stderr.contains("the ROM holds 2"));
+}
+
+#[test]
+fn test_cli_prints_dialect_warnings() {
+    let (status, stdout, stderr) = run_cli(&["-"], "D=A+D\n");
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, "1110000010010000\n");
+    assert!(stderr.contains("warning: `A+D` is not a standard comp"));
+
+    let (status, _, stderr) = run_cli(&["--dialect", "permissive", "-"], "D=A+D\n");
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn test_cli_resolves_includes_and_quotes_included_files() {
+    let dir = scratch_dir("include");
+    fs::create_dir_all(dir.join("std")).unwrap();
+    fs::write(dir.join("Main.asm"), ".include \"util.asm\"\n@1\n").unwrap();
+    fs::write(dir.join("std").join("util.asm"), "D=A\nM=X\n").unwrap();
+
+    let main = dir.join("Main.asm");
+    let std = dir.join("std");
+    let (status, _, stderr) = run_cli(&["-I", std.to_str().unwrap(), "-o", "-", main.to_str().unwrap()], "");
+
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(stderr.contains(&format!("--> {}:2:3", std.join("util.asm").display())));
+    assert!(stderr.contains("2 | M=X"));
+}
+
+#[test]
+fn test_cli_runs_programs_with_includes() {
+    let dir = scratch_dir("run_include");
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::create_dir_all(dir.join("std")).unwrap();
+    fs::write(dir.join("Main.asm"), ".include lib/set.asm\n.include halt.asm\n").unwrap();
+    fs::write(dir.join("lib").join("set.asm"), "@42\nD=A\n@R0\nM=D\n").unwrap();
+    fs::write(dir.join("std").join("halt.asm"), "(END)\n@END\n0;JMP\n").unwrap();
+
+    let main = dir.join("Main.asm");
+    let std = dir.join("std");
+    let (status, stdout, stderr) = run_cli(&["run", "-I", std.to_str().unwrap(), "--ram", "0", main.to_str().unwrap()], "");
+
+    assert_eq!(status, EXIT_SUCCESS, "{}", stderr);
+    assert!(stdout.ends_with("RAM[0] = 42\n"));
+}
+
+#[test]
+fn test_cli_assembles_objects_and_links_them() {
+    let dir = scratch_dir("link");
+    fs::write(dir.join("Main.asm"), ".extern LIB\n@LIB\n0;JMP\n").unwrap();
+    fs::write(dir.join("Lib.asm"), ".export LIB\n(LIB)\n@LIB\n0;JMP\n").unwrap();
+
+    let (main, lib) = (dir.join("Main.asm"), dir.join("Lib.asm"));
+    let (status, _, _) = run_cli(&["--object", main.to_str().unwrap(), lib.to_str().unwrap()], "");
+    assert_eq!(status, EXIT_SUCCESS);
+
+    let (main, lib) = (dir.join("Main.hobj"), dir.join("Lib.hobj"));
+    let (status, stdout, _) = run_cli(&["link", "-o", "-", main.to_str().unwrap(), lib.to_str().unwrap()], "");
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, "0000000000000010\n1110101010000111\n0000000000000010\n1110101010000111\n");
+
+    let (status, _, stderr) = run_cli(&["link", main.to_str().unwrap()], "");
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(stderr.contains("error: `Main` imports `LIB`, which no module exports"));
+}
+
+#[test]
+fn test_cli_passes_defines_to_conditionals() {
+    let source = ".if DEBUG\n@1\n.else\n@0\n.endif\n";
+    let (_, stdout, _) = run_cli(&["-D", "DEBUG", "-"], source);
+    assert_eq!(stdout, "0000000000000001\n");
+
+    let (_, stdout, _) = run_cli(&["--define", "DEBUG=0x0", "-"], source);
+    assert_eq!(stdout, "0000000000000000\n");
+
+    let (status, _, stderr) = run_cli(&["-D", "1X=2", "-"], source);
+    assert_eq!(status, EXIT_USAGE);
+    assert!(stderr.contains("invalid name `1X` for `-D`"));
+}
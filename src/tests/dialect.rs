@@ -0,0 +1,158 @@
+use crate::disassembler::read_words;
+use crate::emulator::Cpu;
+use crate::parser::compiler::{assemble, compile, CompileOptions};
+use crate::parser::dialect::{Dialect, Rule};
+use crate::parser::error::{AssembleError, Severity};
+use crate::parser::span::Span;
+
+const EXTENDED_ASM: &str = "# counter\n@ 5\nd = a /* load */\n(LOOP)\nD=1+D\n@LOOP\nd;jgt\n";
+const STANDARD_ASM: &str = "// counter\n@5\nD=A // load\n(LOOP)\nD=D+1\n@LOOP\nD;JGT\n";
+
+fn rules(source: &str, dialect: Dialect) -> Vec<(Rule, usize)> {
+    let options = CompileOptions { dialect, ..CompileOptions::default() };
+    assemble(String::from(source), &options).unwrap().warnings.iter()
+        .map(|warning| match &warning.error {
+            AssembleError::NonStandard(rule, _) => (*rule, warning.span.line),
+            error => panic!("unexpected warning {}", error),
+        })
+        .collect()
+}
+
+#[test]
+fn test_extensions_assemble_like_standard_hack() {
+    assert_eq!(compile(String::from(EXTENDED_ASM)).unwrap(), compile(String::from(STANDARD_ASM)).unwrap());
+}
+
+#[test]
+fn test_strict_dialect_names_each_rule() {
+    assert_eq!(rules(EXTENDED_ASM, Dialect::Strict), vec![
+        (Rule::CommentStyle, 1),
+        (Rule::Whitespace, 2),
+        (Rule::Whitespace, 3),
+        (Rule::CaseInsensitive, 3),
+        (Rule::CaseInsensitive, 3),
+        (Rule::CommentStyle, 3),
+        (Rule::CommutativeComp, 5),
+        (Rule::CaseInsensitive, 7),
+        (Rule::CaseInsensitive, 7),
+    ]);
+    assert!(rules(STANDARD_ASM, Dialect::Strict).is_empty());
+}
+
+#[test]
+fn test_permissive_dialect_is_silent() {
+    assert!(rules(EXTENDED_ASM, Dialect::Permissive).is_empty());
+}
+
+#[test]
+fn test_tabs_are_whitespace_in_strict_dialect() {
+    let source = "\t@\t5\nD\t=\tM\t// load\n";
+    assert_eq!(compile(String::from(source)).unwrap(), compile(String::from("@5\nD=M\n")).unwrap());
+    assert_eq!(rules(source, Dialect::Strict), vec![(Rule::Whitespace, 1), (Rule::Whitespace, 2)]);
+}
+
+#[test]
+fn test_tabs_are_whitespace_in_permissive_dialect() {
+    let options = CompileOptions { dialect: Dialect::Permissive, ..CompileOptions::default() };
+    let assembly = assemble(String::from(".equ\tN\t5\n@N\nD\t=\tM\n"), &options).unwrap();
+
+    assert_eq!(assembly.to_hack(), compile(String::from("@5\nD=M\n")).unwrap());
+    assert!(assembly.warnings.is_empty());
+}
+
+#[test]
+fn test_whitespace_cannot_split_a_token() {
+    for dialect in [Dialect::Strict, Dialect::Permissive].iter() {
+        let options = CompileOptions { dialect: *dialect, ..CompileOptions::default() };
+        let diagnostics = assemble(String::from("@1 2\n(LO OP)\n@FOO\tBAR\nD=M +1\n"), &options).err().unwrap();
+        let errors: Vec<(&AssembleError, Span)> = diagnostics.iter().map(|diagnostic| (&diagnostic.error, diagnostic.span)).collect();
+
+        assert_eq!(errors, vec![
+            (&AssembleError::Syntax(String::from("whitespace splits the constant `1 2`")), Span::new(1, 2, 3)),
+            (&AssembleError::Syntax(String::from("whitespace splits the label `LO OP`")), Span::new(2, 2, 5)),
+            (&AssembleError::Syntax(String::from("whitespace splits the symbol `FOO BAR`")), Span::new(3, 2, 7)),
+            (&AssembleError::Syntax(String::from("whitespace splits the mnemonic `M +1`")), Span::new(4, 3, 4)),
+        ]);
+    }
+}
+
+#[test]
+fn test_warning_renders_with_rule() {
+    let warning = assemble(String::from("D=A+D\n"), &CompileOptions::default()).unwrap().warnings.remove(0);
+
+    assert_eq!(warning.severity, Severity::Warning);
+    assert_eq!(warning.span, Span::new(1, 3, 3));
+    assert!(warning.render("Add.asm", "D=A+D\n").starts_with(
+        "warning: `A+D` is not a standard comp, the course writes `D+A` (rule `commutative-comp`)\n"
+    ));
+}
+
+#[test]
+fn test_block_comments_span_lines() {
+    assert_eq!(compile(String::from("/* a\n   b */ @1\n")).unwrap(), "0000000000000001");
+    assert!(matches!(compile(String::from("@1 /* open\n")).unwrap_err()[0].error, AssembleError::Syntax(_)));
+}
+
+fn permissive() -> CompileOptions {
+    CompileOptions { dialect: Dialect::Permissive, ..CompileOptions::default() }
+}
+
+#[test]
+fn test_strict_dialect_rejects_wide_constants() {
+    let diagnostics = compile(String::from("@40000\n@-1\n@70000\n@32767\n")).unwrap_err();
+
+    assert_eq!(diagnostics.len(), 3);
+    assert_eq!(diagnostics[0].error, AssembleError::ConstantOutOfRange(String::from("40000")));
+    assert_eq!(diagnostics[0].span, Span::new(1, 2, 5));
+    assert!(diagnostics[0].notes[0].contains("permissive dialect"));
+    assert_eq!(diagnostics[1].error, AssembleError::ConstantOutOfRange(String::from("-1")));
+    assert_eq!(diagnostics[2].notes, vec![String::from("note: constants must fit in 16 bits")]);
+}
+
+#[test]
+fn test_permissive_dialect_expands_wide_constants() {
+    let assembly = assemble(String::from("@-1\n(NEXT)\n@40000\n@NEXT\n"), &permissive()).unwrap();
+
+    assert_eq!(assembly.words, vec![
+        "0000000000000000", "1110110001100000",
+        "0110001110111111", "1110110001100000",
+        "0000000000000010",
+    ]);
+    assert_eq!(assembly.labels[0].address, 2);
+    let lines: Vec<usize> = assembly.spans.iter().map(|span| span.line).collect();
+    assert_eq!(lines, vec![1, 1, 3, 3, 4]);
+}
+
+#[test]
+fn test_wide_constants_load_their_value() {
+    let hack = assemble(String::from("@-5\nD=A\n@0\nM=D\n@65535\nD=A\n@1\nM=D\n"), &permissive()).unwrap().to_hack();
+    let mut cpu = Cpu::new();
+    cpu.load(&read_words(&hack).unwrap()).unwrap();
+    cpu.run(100);
+
+    assert_eq!(cpu.ram()[0] as i16, -5);
+    assert_eq!(cpu.ram()[1], 65535);
+}
+
+#[test]
+fn test_hex_binary_and_char_literals() {
+    let source = "@0x4000\n@0b1010\n@'A'\n@' '\n@'\\n'\n@'\\b'\n@'\\''\n@1_000\n@0X7F_FF\n";
+    let expected = compile(String::from("@16384\n@10\n@65\n@32\n@128\n@129\n@39\n@1000\n@32767\n")).unwrap();
+
+    assert_eq!(compile(String::from(source)).unwrap(), expected);
+    assert_eq!(rules(source, Dialect::Strict), (1..=9).map(|line| (Rule::LiteralSyntax, line)).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_invalid_literals_are_reported() {
+    let diagnostics = compile(String::from("@0x\n@0b102\n@'ab'\n@'\\q'\n@0x10000\n")).unwrap_err();
+    let errors: Vec<&AssembleError> = diagnostics.iter().map(|diagnostic| &diagnostic.error).collect();
+
+    assert_eq!(errors, vec![
+        &AssembleError::Lexical(String::from("invalid constant `0x`")),
+        &AssembleError::Lexical(String::from("invalid constant `0b102`")),
+        &AssembleError::Syntax(String::from("unterminated character constant, expected `'`")),
+        &AssembleError::Lexical(String::from("unknown escape `\\q`")),
+        &AssembleError::ConstantOutOfRange(String::from("0x10000")),
+    ]);
+}
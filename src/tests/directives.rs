@@ -0,0 +1,79 @@
+use crate::parser::compiler::{assemble, compile, CompileOptions};
+use crate::parser::error::AssembleError;
+use crate::parser::parser::SymbolKind;
+use crate::parser::span::Span;
+
+#[test]
+fn test_constants_resolve_without_ram() {
+    let source = "@ROWS\n.equ ROWS 256\n.define MASK, 0x00FF\n@MASK\n@counter\n";
+    assert_eq!(compile(String::from(source)).unwrap(), compile(String::from("@256\n@255\n@16\n")).unwrap());
+
+    let assembly = assemble(String::from(source), &CompileOptions::default()).unwrap();
+    let constants: Vec<(&str, u32)> = assembly.symbols.iter()
+        .filter(|symbol| symbol.kind == SymbolKind::Constant)
+        .map(|symbol| (symbol.name.as_str(), symbol.address))
+        .collect();
+    assert_eq!(constants, vec![("ROWS", 256), ("MASK", 255)]);
+}
+
+#[test]
+fn test_constant_redefinition_is_reported() {
+    let diagnostics = compile(String::from(".equ N 1\n(LOOP)\n.equ N 2\n.equ LOOP 3\n.equ SCREEN 0\n")).unwrap_err();
+    let errors: Vec<(&AssembleError, usize, &[String])> = diagnostics.iter()
+        .map(|diagnostic| (&diagnostic.error, diagnostic.span.line, diagnostic.notes.as_slice()))
+        .collect();
+
+    assert_eq!(errors, vec![
+        (&AssembleError::Redefinition(String::from("N")), 3, &[String::from("note: previously defined on line 1")][..]),
+        (&AssembleError::Redefinition(String::from("LOOP")), 4, &[String::from("note: previously defined on line 2")][..]),
+        (&AssembleError::Redefinition(String::from("SCREEN")), 5, &[String::from("note: `SCREEN` is a predefined symbol")][..]),
+    ]);
+}
+
+#[test]
+fn test_malformed_directives_are_reported() {
+    let diagnostics = compile(String::from(".equ\n.equ N\n.equ N 32768\n.org 5\n")).unwrap_err();
+    let errors: Vec<&AssembleError> = diagnostics.iter().map(|diagnostic| &diagnostic.error).collect();
+
+    assert_eq!(errors, vec![
+        &AssembleError::Syntax(String::from("expected a symbol name after `.equ`")),
+        &AssembleError::Syntax(String::from("expected a constant value for `N`")),
+        &AssembleError::ConstantOutOfRange(String::from("32768")),
+        &AssembleError::Lexical(String::from("unknown directive `.org`")),
+    ]);
+    assert_eq!(diagnostics[3].span, Span::new(4, 1, 4));
+}
+
+#[test]
+fn test_dot_labels_are_scoped_to_the_previous_label() {
+    let source = "(MULT)\n(.loop)\n@.loop\n0;JMP\n(DIV)\n@.loop\n(.loop)\n@MULT.loop\n";
+    let assembly = assemble(String::from(source), &CompileOptions::default()).unwrap();
+
+    let labels: Vec<(&str, u32)> = assembly.labels.iter().map(|label| (label.name.as_str(), label.address)).collect();
+    assert_eq!(labels, vec![("MULT", 0), ("MULT.loop", 0), ("DIV", 2), ("DIV.loop", 3)]);
+    assert_eq!(assembly.to_hack(), compile(String::from("@0\n0;JMP\n@3\n@0\n")).unwrap());
+}
+
+#[test]
+fn test_numeric_labels_resolve_backwards_and_forwards() {
+    let source = "1:\n@1f\n0;JMP\n1:\n@1b\n@2f\n2:\n@1b\n@0b101\n";
+    let assembly = assemble(String::from(source), &CompileOptions::default()).unwrap();
+
+    assert_eq!(assembly.to_hack(), compile(String::from("@2\n0;JMP\n@2\n@4\n@2\n@5\n")).unwrap());
+    assert!(assembly.symbols.iter().all(|symbol| symbol.kind != SymbolKind::Variable));
+
+    let diagnostics = compile(String::from("@1b\n1:\n@2f\n")).unwrap_err();
+    let errors: Vec<(&AssembleError, &str)> = diagnostics.iter()
+        .map(|diagnostic| (&diagnostic.error, diagnostic.notes[0].as_str()))
+        .collect();
+    assert_eq!(errors, vec![
+        (&AssembleError::UnresolvedSymbol(String::from("1b")), "note: there is no `1:` label before this instruction"),
+        (&AssembleError::UnresolvedSymbol(String::from("2f")), "note: there is no `2:` label after this instruction"),
+    ]);
+}
+
+#[test]
+fn test_non_ascii_symbols_are_not_numeric_references() {
+    let source = "(é)\n@é\n@xé\n@é\n";
+    assert_eq!(compile(String::from(source)).unwrap(), compile(String::from("@0\n@16\n@0\n")).unwrap());
+}
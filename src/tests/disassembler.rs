@@ -0,0 +1,73 @@
+use crate::disassembler::{disassemble, DisassembleOptions};
+use crate::parser::compiler::compile;
+use crate::parser::table::DestStyle;
+use super::fixtures::*;
+
+fn assert_round_trip(asm: &str, options: &DisassembleOptions) {
+    let hack = compile(String::from(asm)).unwrap();
+    let disassembled = disassemble(&hack, options).unwrap();
+    assert_eq!(compile(disassembled).unwrap(), hack);
+}
+
+#[test]
+fn test_disassembler_round_trips_fixtures() {
+    let plain = DisassembleOptions { labels: false, symbols: false, dest_style: DestStyle::Classic };
+    for asm in &[ADD_ASM, MAX_ASM, MAXL_ASM, RECT_ASM, PONGL_ASM, PONG_ASM] {
+        assert_round_trip(asm, &DisassembleOptions::default());
+        assert_round_trip(asm, &plain);
+    }
+}
+
+#[test]
+fn test_disassembler_restores_add_asm() {
+    let options = DisassembleOptions::default();
+    assert_eq!(disassemble(ADD_HACK, &options).unwrap(), "@2\nD=A\n@3\nD=D+A\n@R0\nM=D\n");
+}
+
+#[test]
+fn test_disassembler_synthesizes_jump_labels() {
+    let options = DisassembleOptions::default();
+    let asm = disassemble(MAX_HACK, &options).unwrap();
+
+    assert!(asm.contains("(L_10)\n@R0\nD=M\n"));
+    assert!(asm.contains("(L_14)\n@L_14\n0;JMP\n"));
+}
+
+#[test]
+fn test_disassembler_rejects_malformed_lines() {
+    let diagnostics = disassemble("0000000000000010\n12\n\n000000000000001x\n", &DisassembleOptions::default()).unwrap_err();
+    let lines: Vec<usize> = diagnostics.iter().map(|diagnostic| diagnostic.span.line).collect();
+
+    assert_eq!(lines, vec![2, 4]);
+}
+
+#[test]
+fn test_disassembler_rejects_invalid_instructions() {
+    let diagnostics = disassemble("0000000000000010\n\n1000000000000000\n", &DisassembleOptions::default()).unwrap_err();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].span.line, 3);
+}
+
+#[test]
+fn test_disassembler_dest_styles() {
+    let hack = compile(String::from("MD=D+1\nAMD=M-1\nAM=0\n")).unwrap();
+    let modern = DisassembleOptions { dest_style: DestStyle::Modern, ..DisassembleOptions::default() };
+
+    assert_eq!(disassemble(&hack, &DisassembleOptions::default()).unwrap(), "MD=D+1\nAMD=M-1\nAM=0\n");
+    assert_eq!(disassemble(&hack, &modern).unwrap(), "DM=D+1\nADM=M-1\nAM=0\n");
+}
+
+#[test]
+fn test_disassembler_round_trips_dest_with_jump() {
+    assert_round_trip("(LOOP)\nD=D-1;JGT\n@LOOP\nAM=M+1;JNE\n", &DisassembleOptions::default());
+}
+
+#[test]
+fn test_disassembler_round_trips_bare_comp() {
+    let hack = "1110101010000000\n1111110010000000\n";
+    let asm = disassemble(hack, &DisassembleOptions::default()).unwrap();
+
+    assert_eq!(asm, "0\nM-1\n");
+    assert_eq!(compile(asm).unwrap(), hack.trim_end());
+}
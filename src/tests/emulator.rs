@@ -0,0 +1,74 @@
+use crate::disassembler::read_words;
+use crate::emulator::{Cpu, RunResult, KBD, SCREEN};
+use crate::parser::compiler::compile;
+use super::fixtures::*;
+
+fn load(asm: &str) -> Cpu {
+    let program = read_words(&compile(String::from(asm)).unwrap()).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load(&program).unwrap();
+    cpu
+}
+
+#[test]
+fn test_emulator_runs_add() {
+    let mut cpu = load(ADD_ASM);
+
+    assert_eq!(cpu.run_until_halt(1000), RunResult::Halted);
+    assert_eq!(cpu.ram()[0], 5);
+}
+
+#[test]
+fn test_emulator_runs_max() {
+    for (first, second, max) in &[(3, 7, 7), (9, 4, 9), (5, 5, 5)] {
+        let mut cpu = load(MAX_ASM);
+        cpu.ram_mut()[0] = *first;
+        cpu.ram_mut()[1] = *second;
+
+        assert_eq!(cpu.run_until_halt(1000), RunResult::Halted);
+        assert_eq!(cpu.ram()[2], *max);
+    }
+}
+
+#[test]
+fn test_emulator_draws_rect_on_screen() {
+    let mut cpu = load(RECT_ASM);
+    cpu.ram_mut()[0] = 4;
+
+    assert_eq!(cpu.run_until_halt(10_000), RunResult::Halted);
+    for row in 0..4 {
+        assert_eq!(cpu.screen()[row * 32], 0xffff);
+    }
+    assert_eq!(cpu.screen()[4 * 32], 0);
+    assert_eq!(cpu.ram()[SCREEN], 0xffff);
+}
+
+#[test]
+fn test_emulator_stops_at_cycle_limit() {
+    let mut cpu = load(ADD_ASM);
+
+    assert_eq!(cpu.run(3), RunResult::CycleLimit);
+    assert_eq!(cpu.pc, 3);
+    assert_eq!(cpu.d, 2);
+    assert_eq!(cpu.cycles(), 3);
+}
+
+#[test]
+fn test_emulator_reads_keyboard() {
+    let mut cpu = load("@KBD\nD=M\n@0\nM=D\n(END)\n@END\n0;JMP\n");
+    cpu.set_key(65);
+
+    assert_eq!(cpu.run_until_halt(100), RunResult::Halted);
+    assert_eq!(cpu.ram()[0], 65);
+    assert_eq!(cpu.ram()[KBD], 65);
+}
+
+#[test]
+fn test_emulator_alu_wraps_to_negative() {
+    let mut cpu = load("@0\nD=A\nD=D-1\n@1\nM=D\nAM=M+1\n");
+
+    assert_eq!(cpu.run_until_halt(100), RunResult::Halted);
+    assert_eq!(cpu.ram()[1], 0);
+    assert_eq!(cpu.a, 0);
+    assert_eq!(cpu.d, 0xffff);
+}
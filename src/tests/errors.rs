@@ -0,0 +1,164 @@
+use crate::parser::compiler::{compile, compile_with_options, CompileOptions};
+use crate::parser::error::{AssembleError, Field};
+use crate::parser::span::Span;
+
+#[test]
+fn test_unknown_comp_is_reported() {
+    let diagnostic = compile(String::from("@1\nD=D+X\n")).unwrap_err().remove(0);
+    assert_eq!(diagnostic.error, AssembleError::UnknownMnemonic(Field::Comp, String::from("D+X")));
+    assert_eq!(diagnostic.span, Span::new(2, 3, 3));
+}
+
+#[test]
+fn test_unknown_jump_is_reported() {
+    let diagnostic = compile(String::from("D;JUMP\n")).unwrap_err().remove(0);
+    assert_eq!(diagnostic.error, AssembleError::UnknownMnemonic(Field::Jump, String::from("JUMP")));
+}
+
+#[test]
+fn test_unterminated_label_is_a_syntax_error() {
+    let result = compile(String::from("(LOOP\n@LOOP\n0;JMP\n"));
+    assert!(matches!(result.unwrap_err()[0].error, AssembleError::Syntax(_)));
+}
+
+#[test]
+fn test_missing_separator_is_a_syntax_error() {
+    let result = compile(String::from("@1\nD M\n"));
+    assert!(matches!(result.unwrap_err()[0].error, AssembleError::Syntax(_)));
+}
+
+#[test]
+fn test_unexpected_character_is_a_lexical_error() {
+    let result = compile(String::from("%include\n"));
+    assert!(matches!(result.unwrap_err()[0].error, AssembleError::Lexical(_)));
+}
+
+#[test]
+fn test_oversized_constant_is_out_of_range() {
+    let diagnostic = compile(String::from("@99999999999\n")).unwrap_err().remove(0);
+    assert_eq!(diagnostic.error, AssembleError::ConstantOutOfRange(String::from("99999999999")));
+    assert_eq!(diagnostic.span, Span::new(1, 2, 11));
+}
+
+#[test]
+fn test_empty_source_compiles_to_nothing() {
+    assert_eq!(compile(String::new()), Ok(String::new()));
+}
+
+#[test]
+fn test_columns_count_skipped_spaces() {
+    let diagnostic = compile(String::from("   @2\n   D = D & X  // mask\n")).unwrap_err().remove(0);
+    assert_eq!(diagnostic.span, Span::new(2, 8, 5));
+}
+
+#[test]
+fn test_diagnostic_renders_source_line_with_caret() {
+    let source = "@1\n  D=D+X\n";
+    let diagnostic = compile(String::from(source)).unwrap_err().remove(0);
+
+    assert_eq!(diagnostic.render("Add.asm", source), "\
+error: unknown comp mnemonic `D+X`
+ --> Add.asm:2:5
+  |
+2 |   D=D+X
+  |     ^^^
+  = help: did you mean `D+1`?
+");
+}
+
+#[test]
+fn test_errors_on_every_line_are_collected() {
+    let diagnostics = compile(String::from("@1\nD=D+X\n(LOOP\n@LOOP\nD;JUMP\n")).unwrap_err();
+    let lines: Vec<usize> = diagnostics.iter().map(|diagnostic| diagnostic.span.line).collect();
+
+    assert_eq!(lines, vec![2, 3, 5]);
+}
+
+#[test]
+fn test_tokenizer_resumes_after_lexical_error() {
+    let diagnostics = compile(String::from("%1\n@2\nD M\nD=A\n")).unwrap_err();
+
+    assert_eq!(diagnostics.len(), 2);
+    assert!(matches!(diagnostics[0].error, AssembleError::Lexical(_)));
+    assert!(matches!(diagnostics[1].error, AssembleError::Syntax(_)));
+}
+
+#[test]
+fn test_error_count_is_capped() {
+    let options = CompileOptions { max_errors: 2, ..CompileOptions::default() };
+    let diagnostics = compile_with_options(String::from("D=X\nD=Y\nD=Z\nD=W\n"), &options).unwrap_err();
+
+    assert_eq!(diagnostics.len(), 2);
+}
+
+#[test]
+fn test_error_cap_keeps_the_earliest_errors() {
+    let options = CompileOptions { max_errors: 2, ..CompileOptions::default() };
+    let diagnostics = compile_with_options(String::from("D=Q\n@\n@\n"), &options).unwrap_err();
+    let lines: Vec<usize> = diagnostics.iter().map(|diagnostic| diagnostic.span.line).collect();
+
+    assert_eq!(lines, vec![1, 2]);
+    assert!(matches!(diagnostics[0].error, AssembleError::UnknownMnemonic(..)));
+}
+
+#[test]
+fn test_commutative_comp_forms_assemble_alike() {
+    let canonical = compile(String::from("D=D+A\nD=D+1\nM=D&M\nAM=D|A\n")).unwrap();
+    let swapped = compile(String::from("D=A+D\nD=1+D\nM=M&D\nAM=A|D\n")).unwrap();
+
+    assert_eq!(canonical, swapped);
+}
+
+#[test]
+fn test_non_commutative_comp_is_not_swapped() {
+    let diagnostic = compile(String::from("D=1-D\n")).unwrap_err().remove(0);
+    assert_eq!(diagnostic.error, AssembleError::UnknownMnemonic(Field::Comp, String::from("1-D")));
+}
+
+#[test]
+fn test_unknown_mnemonic_suggests_closest() {
+    let diagnostic = compile(String::from("0;JUMP\n")).unwrap_err().remove(0);
+    assert_eq!(diagnostic.notes, vec![String::from("help: did you mean `JMP`?")]);
+    assert!(diagnostic.render("Jump.asm", "0;JUMP\n").ends_with("  |   ^^^^\n  = help: did you mean `JMP`?\n"));
+
+    let diagnostic = compile(String::from("0;jgx\n")).unwrap_err().remove(0);
+    assert_eq!(diagnostic.notes, vec![String::from("help: did you mean `JGT`?")]);
+
+    let diagnostic = compile(String::from("D=d|z\n")).unwrap_err().remove(0);
+    assert_eq!(diagnostic.notes, vec![String::from("help: did you mean `D|A`?")]);
+
+    let diagnostic = compile(String::from("D=Q\n")).unwrap_err().remove(0);
+    assert!(diagnostic.notes.is_empty());
+}
+
+#[test]
+fn test_dest_accepts_any_register_order() {
+    let canonical = compile(String::from("MD=1\nAM=1\nAD=1\nAMD=1\n")).unwrap();
+    let permuted = compile(String::from("DM=1\nMA=1\nDA=1\nDAM=1\n")).unwrap();
+
+    assert_eq!(canonical, permuted);
+}
+
+#[test]
+fn test_dest_rejects_repeated_and_unknown_registers() {
+    let diagnostics = compile(String::from("MM=1\nMX=1\n=1\n")).unwrap_err();
+
+    assert_eq!(diagnostics[0].error, AssembleError::RepeatedRegister(String::from("MM"), 'M'));
+    assert_eq!(diagnostics[0].span, Span::new(1, 1, 2));
+    assert_eq!(diagnostics[1].error, AssembleError::UnknownMnemonic(Field::Dest, String::from("MX")));
+    assert_eq!(diagnostics[2].span.line, 3);
+}
+
+#[test]
+fn test_jng_is_not_a_jump() {
+    let diagnostic = compile(String::from("0;JNG\n")).unwrap_err().remove(0);
+
+    assert_eq!(diagnostic.error, AssembleError::UnknownMnemonic(Field::Jump, String::from("JNG")));
+    assert_eq!(diagnostic.span, Span::new(1, 3, 3));
+}
+
+#[test]
+fn test_dest_comp_and_jump_together() {
+    assert_eq!(compile(String::from("D=M;JGT\nAM=M-1;JMP\n")).unwrap(), "1111110000010001\n1111110010101111");
+    assert_eq!(compile(String::from("D=M\n")).unwrap(), "1111110000010000");
+}
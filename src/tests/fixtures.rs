@@ -111005,4 +111005,31 @@ pub const PONGL_HACK: &str = r#"0000000100000000
 1111110010101000
 1111110000010000
 0000000000000101
-1110001100001000"#;
\ No newline at end of file
+1110001100001000"#;
+pub const MAX_TST: &str = r#"// Tests Max.asm on the CPU emulator.
+
+load Max.asm,
+output-file Max.out,
+compare-to Max.cmp,
+output-list RAM[0]%D2.6.2 RAM[1]%D2.6.2 RAM[2]%D2.6.2;
+
+set RAM[0] 15,   // Set test arguments
+set RAM[1] 32;
+repeat 14 {
+  ticktock;
+}
+output;
+
+set PC 0,
+set RAM[0] 47,
+set RAM[1] 22;
+repeat 14 {
+  ticktock;
+}
+output;
+"#;
+
+pub const MAX_CMP: &str = r#"|  RAM[0]  |  RAM[1]  |  RAM[2]  |
+|      15  |      32  |      32  |
+|      47  |      22  |      47  |
+"#;
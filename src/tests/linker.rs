@@ -0,0 +1,101 @@
+use crate::linker::link;
+use crate::linker::object::{Object, Relocation, Target};
+use crate::linker::LinkError;
+use crate::parser::compiler::{assemble, compile, CompileOptions};
+use crate::parser::error::AssembleError;
+use crate::parser::span::Span;
+
+const MAIN_ASM: &str = ".extern DOUBLE\n@5\nD=A\n@x\nM=D\n@DOUBLE\n0;JMP\n(END)\n@END\n0;JMP\n";
+const DOUBLE_ASM: &str = ".export DOUBLE\n(DOUBLE)\n@x\nD=M\n@y\nM=D\n(LOOP)\n@LOOP\n0;JMP\n";
+
+fn module(name: &str, source: &str) -> Object {
+    let options = CompileOptions { relocatable: true, ..CompileOptions::default() };
+    Object::from_assembly(name, &assemble(String::from(source), &options).unwrap())
+}
+
+#[test]
+fn test_modules_link_like_one_program() {
+    let main = module("Main", MAIN_ASM);
+    assert_eq!(main.imports, vec![String::from("DOUBLE")]);
+    assert_eq!(main.relocations, vec![
+        Relocation { address: 2, target: Target::Variable(String::from("x")) },
+        Relocation { address: 4, target: Target::Import(String::from("DOUBLE")) },
+        Relocation { address: 6, target: Target::Label },
+    ]);
+    let double = module("Double", DOUBLE_ASM);
+    assert_eq!(double.exports, vec![(String::from("DOUBLE"), 0)]);
+
+    let words: Vec<String> = link(&[main, double]).unwrap().iter().map(|word| format!("{:016b}", word)).collect();
+    let program = format!("{}{}", &MAIN_ASM[".extern DOUBLE\n".len()..], &DOUBLE_ASM[".export DOUBLE\n".len()..]);
+    assert_eq!(words.join("\n"), compile(program).unwrap());
+}
+
+#[test]
+fn test_objects_survive_a_round_trip() {
+    let object = module("Main", MAIN_ASM);
+    let text = object.write();
+
+    assert!(text.starts_with("hack-object 1\nmodule Main\nimport DOUBLE\ncode 8\n0005\n"));
+    assert!(text.ends_with("reloc 2 variable x\nreloc 4 import DOUBLE\nreloc 6 label\n"));
+    assert_eq!(Object::parse(&text).unwrap(), object);
+    assert!(Object::parse("hack-object 1\ncode 1\n0005\nreloc 3 label\n").is_err());
+    assert!(Object::parse("hack-object 1\ncode 1\n0005\nreloc 0 import DOUBLE\n").is_err());
+    for address in &["32768", "65536"] {
+        let text = format!("hack-object 1\nexport FAR {}\ncode 1\n0005\n", address);
+        assert_eq!(Object::parse(&text).unwrap_err(), format!("line 2: export address {} is past the 32767 an A-instruction can load", address));
+    }
+}
+
+#[test]
+fn test_link_errors_are_reported() {
+    let errors = link(&[module("Main", MAIN_ASM), module("A", DOUBLE_ASM), module("B", DOUBLE_ASM)]).unwrap_err();
+    assert_eq!(errors, vec![LinkError::DuplicateExport(String::from("DOUBLE"), String::from("A"), String::from("B"))]);
+
+    let errors = link(&[module("Main", MAIN_ASM)]).unwrap_err();
+    assert_eq!(errors, vec![LinkError::UnresolvedImport(String::from("DOUBLE"), String::from("Main"))]);
+
+    let errors = link(&[module("Unused", ".extern NOWHERE\n@1\n")]).unwrap_err();
+    assert_eq!(errors, vec![LinkError::UnresolvedImport(String::from("NOWHERE"), String::from("Unused"))]);
+}
+
+#[test]
+fn test_out_of_range_addresses_name_their_memory() {
+    let far = Object {
+        name: String::from("Far"),
+        code: vec![40000],
+        exports: vec![],
+        imports: vec![],
+        relocations: vec![Relocation { address: 0, target: Target::Label }],
+    };
+    let errors = link(&[far]).unwrap_err();
+    assert_eq!(errors, vec![LinkError::AddressOutOfRange(String::from("Far"), Target::Label, 40000)]);
+    assert_eq!(errors[0].to_string(), "`Far` refers to ROM address 40000, past the 32767 an A-instruction can load");
+
+    let error = LinkError::AddressOutOfRange(String::from("Big"), Target::Variable(String::from("x")), 32768);
+    assert_eq!(error.to_string(), "`Big` refers to variable `x` at RAM address 32768, past the 32767 an A-instruction can load");
+}
+
+#[test]
+fn test_imports_and_exports_need_a_module() {
+    let diagnostics = compile(String::from(MAIN_ASM)).unwrap_err();
+    assert_eq!(diagnostics[0].error, AssembleError::UnresolvedSymbol(String::from("DOUBLE")));
+    assert_eq!(diagnostics[0].span.line, 6);
+
+    let diagnostics = compile(String::from(".export MISSING\n.extern END\n(END)\n")).unwrap_err();
+    let errors: Vec<&AssembleError> = diagnostics.iter().map(|diagnostic| &diagnostic.error).collect();
+    assert_eq!(errors, vec![
+        &AssembleError::UnresolvedSymbol(String::from("MISSING")),
+        &AssembleError::Redefinition(String::from("END")),
+    ]);
+}
+
+#[test]
+fn test_labels_are_exported_once() {
+    let options = CompileOptions { relocatable: true, ..CompileOptions::default() };
+    let diagnostics = assemble(String::from(".export LOOP\n(LOOP)\n.export LOOP\n"), &options).err().unwrap();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].error, AssembleError::Syntax(String::from("`LOOP` is already exported")));
+    assert_eq!(diagnostics[0].span, Span::new(3, 1, 12));
+    assert_eq!(diagnostics[0].notes, vec![String::from("note: previously exported on line 1")]);
+}
@@ -1,2 +1,13 @@
 mod integration;
+mod cli;
+mod errors;
+mod disassembler;
+mod emulator;
+mod tester;
+mod vm;
+mod output;
+mod dialect;
+mod directives;
+mod preprocessor;
+mod linker;
 pub mod fixtures;
\ No newline at end of file
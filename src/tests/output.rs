@@ -0,0 +1,113 @@
+use crate::output::format::{find_format, FormatOptions};
+use crate::output::json;
+use crate::output::listing::listing;
+use crate::output::source_map::source_map;
+use crate::output::symbols::{sym_file, symbols_json};
+use crate::parser::compiler::{assemble, CompileOptions};
+
+const LOOP_ASM: &str = "// count down\n@3\nD=A\n(LOOP)\nD=D-1 // step\n@LOOP\nD;JGT\n";
+
+#[test]
+fn test_listing_shows_words_next_to_source() {
+    let assembly = assemble(String::from(LOOP_ASM), &CompileOptions::default()).unwrap();
+    let rows: Vec<&str> = LOOP_ASM.lines().collect();
+    let expected = format!(concat!(
+        "  ROM  HEX   BINARY             LINE  SOURCE\n",
+        "                                   1  {}\n",
+        "    0  0003  0000000000000011      2  {}\n",
+        "    1  EC10  1110110000010000      3  {}\n",
+        "    2                              4  {}\n",
+        "    2  E390  1110001110010000      5  {}\n",
+        "    3  0002  0000000000000010      6  {}\n",
+        "    4  E301  1110001100000001      7  {}\n",
+    ), rows[0], rows[1], rows[2], rows[3], rows[4], rows[5], rows[6]);
+
+    assert_eq!(listing(&assembly), expected);
+}
+
+#[test]
+fn test_assembly_keeps_labels_and_spans() {
+    let assembly = assemble(String::from(LOOP_ASM), &CompileOptions::default()).unwrap();
+
+    assert_eq!(assembly.labels.len(), 1);
+    assert_eq!((assembly.labels[0].name.as_str(), assembly.labels[0].address, assembly.labels[0].span.line), ("LOOP", 2, 4));
+    let lines: Vec<usize> = assembly.spans.iter().map(|span| span.line).collect();
+    assert_eq!(lines, vec![2, 3, 5, 6, 7]);
+}
+
+#[test]
+fn test_symbol_table_distinguishes_kinds() {
+    let source = String::from("@counter\nM=0\n(LOOP)\n@sum\nM=M+1\n@LOOP\n0;JMP\n");
+    let assembly = assemble(source, &CompileOptions::default()).unwrap();
+    let sym = sym_file(&assembly);
+
+    assert!(sym.starts_with("predefined     0  SP\n"));
+    assert!(sym.ends_with("label          2  LOOP\nvariable      16  counter\nvariable      17  sum\n"));
+    assert!(symbols_json(&assembly).contains("{\"name\": \"LOOP\", \"address\": 2, \"kind\": \"label\"},\n    {\"name\": \"counter\", \"address\": 16, \"kind\": \"variable\"}"));
+}
+
+#[test]
+fn test_json_strings_are_escaped() {
+    assert_eq!(json::string("a\"b\\c\n\u{1}"), "\"a\\\"b\\\\c\\n\\u0001\"");
+}
+
+#[test]
+fn test_source_map_points_addresses_at_source() {
+    let assembly = assemble(String::from(LOOP_ASM), &CompileOptions::default()).unwrap();
+    let map = source_map(&assembly, "Loop.asm");
+
+    assert!(map.contains("\"file\": \"Loop.asm\""));
+    assert!(map.contains("{\"address\": 2, \"file\": \"Loop.asm\", \"line\": 5, \"column\": 1, \"length\": 5}"));
+    assert!(map.contains("{\"address\": 4, \"file\": \"Loop.asm\", \"line\": 7, \"column\": 1, \"length\": 5}"));
+    assert!(map.contains("{\"name\": \"LOOP\", \"address\": 2, \"file\": \"Loop.asm\", \"line\": 4, \"column\": 1, \"length\": 6}"));
+}
+
+#[test]
+fn test_formats_write_words() {
+    let words = [0x0003, 0xEC10];
+    let text = |name: &str| String::from_utf8(find_format(name).unwrap().write(&words, &FormatOptions::default())).unwrap();
+
+    assert_eq!(text("hack"), "0000000000000011\n1110110000010000\n");
+    assert_eq!(text("hex"), "0003\nEC10\n");
+    assert_eq!(find_format("bin").unwrap().write(&words, &FormatOptions::default()), vec![0x00, 0x03, 0xEC, 0x10]);
+    assert_eq!(find_format("bin-le").unwrap().write(&words, &FormatOptions::default()), vec![0x03, 0x00, 0x10, 0xEC]);
+    assert!(find_format("elf").is_none());
+}
+
+#[test]
+fn test_intel_hex_records_have_checksums() {
+    let words: Vec<u16> = (0..9).collect();
+    let ihex = String::from_utf8(find_format("ihex").unwrap().write(&words, &FormatOptions::default())).unwrap();
+
+    assert_eq!(ihex, ":1000000000000001000200030004000500060007D4\n:020010000008E6\n:00000001FF\n");
+}
+
+#[test]
+fn test_logisim_image_compresses_runs() {
+    let words = [0x10, 0, 0, 0, 0, 0xE308, 0xE308];
+    let image = String::from_utf8(find_format("logisim").unwrap().write(&words, &FormatOptions::default())).unwrap();
+
+    assert_eq!(image, "v2.0 raw\n10 4*0 e308 e308\n");
+}
+
+#[test]
+fn test_memory_files_are_padded_to_rom_size() {
+    let options = FormatOptions { rom_size: 4 };
+    let write = |name: &str| String::from_utf8(find_format(name).unwrap().write(&[0x0003, 0xEC10], &options)).unwrap();
+
+    assert_eq!(write("readmemh"), "0003\nec10\n0000\n0000\n");
+    assert_eq!(write("readmemb"), "0000000000000011\n1110110000010000\n0000000000000000\n0000000000000000\n");
+}
+
+#[test]
+fn test_hdl_roms_bake_in_words() {
+    let options = FormatOptions { rom_size: 1024 };
+    let vhdl = String::from_utf8(find_format("vhdl").unwrap().write(&[0x0003, 0xEC10], &options)).unwrap();
+    let verilog = String::from_utf8(find_format("verilog").unwrap().write(&[0x0003, 0xEC10], &options)).unwrap();
+
+    assert!(vhdl.contains("address : in  std_logic_vector(9 downto 0);"));
+    assert!(vhdl.contains("array (0 to 1023)"));
+    assert!(vhdl.contains("        1 => x\"EC10\",\n        others => x\"0000\"\n"));
+    assert!(verilog.contains("input  wire [9:0] address,"));
+    assert!(verilog.contains("10'd1: data = 16'hEC10;\n            default: data = 16'h0000;"));
+}
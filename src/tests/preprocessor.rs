@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use crate::output::listing::listing;
+use crate::output::source_map::source_map;
+use crate::parser::compiler::{assemble, assemble_with_loader, compile, Assembly, CompileOptions};
+use crate::parser::error::{AssembleError, Diagnostic, Field};
+use crate::parser::span::Span;
+
+const PUSH_ASM: &str = "\
+.macro PUSH_D
+    @SP
+    A=M
+    M=D
+    @SP
+    M=M+1
+.endm
+.macro PUSH_CONST value
+    @\\value  // load
+    D=A
+    PUSH_D
+.endm
+PUSH_CONST 7
+PUSH_CONST 0x10
+";
+
+#[test]
+fn test_macros_expand_with_arguments() {
+    let expanded = "@7\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1\n@16\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1\n";
+    assert_eq!(compile(String::from(PUSH_ASM)).unwrap(), compile(String::from(expanded)).unwrap());
+
+    // Every expanded word points at its call.
+    let assembly = assemble(String::from(PUSH_ASM), &CompileOptions::default()).unwrap();
+    assert_eq!(assembly.spans[0], Span::new(13, 1, 12));
+    assert_eq!(assembly.spans[13], Span::new(14, 1, 15));
+}
+
+#[test]
+fn test_macro_labels_are_unique_per_expansion() {
+    let source = ".macro WAIT\n(LOOP)\n@KBD\nD=M\n@LOOP\nD;JEQ\n.endm\nWAIT\nWAIT\n@LOOP\n";
+    let assembly = assemble(String::from(source), &CompileOptions::default()).unwrap();
+
+    let labels: Vec<(&str, u32)> = assembly.labels.iter().map(|label| (label.name.as_str(), label.address)).collect();
+    assert_eq!(labels, vec![("LOOP$WAIT$1", 0), ("LOOP$WAIT$2", 4)]);
+    assert_eq!(&assembly.words[2..4], ["0000000000000000", "1110001100000010"]);
+    assert_eq!(&assembly.words[6..9], ["0000000000000100", "1110001100000010", "0000000000010000"]);
+}
+
+#[test]
+fn test_errors_in_macro_bodies_point_at_call_and_body() {
+    let source = ".macro INC var\n    @\\var\n    M=M+X\n.endm\n@1\n  INC counter\n";
+    let diagnostic = compile(String::from(source)).unwrap_err().remove(0);
+
+    assert_eq!(diagnostic.span, Span::new(6, 3, 11));
+    assert_eq!(diagnostic.error, AssembleError::UnknownMnemonic(Field::Comp, String::from("M+X")));
+    assert_eq!(diagnostic.notes.last().unwrap(), "note: expanded from macro `INC` at line 3");
+}
+
+#[test]
+fn test_block_comments_span_macro_body_lines() {
+    let source = ".macro TWICE\n/* TWICE calls\nTWICE\n@\\count\n*/ D=D+1\n.endm\nTWICE\n";
+    assert_eq!(compile(String::from(source)).unwrap(), compile(String::from("D=D+1\n")).unwrap());
+
+    let source = ".macro INC\n/*\n.endm\n*/\nD=D+1\n.endm\nINC\nINC\n";
+    assert_eq!(compile(String::from(source)).unwrap(), compile(String::from("D=D+1\nD=D+1\n")).unwrap());
+}
+
+#[test]
+fn test_recursive_macros_are_limited() {
+    let diagnostic = compile(String::from(".macro LOOP\nD=D+1\nLOOP\n.endm\nLOOP\n")).unwrap_err().remove(0);
+
+    assert_eq!(diagnostic.error, AssembleError::Syntax(String::from("macro `LOOP` expands more than 16 levels deep")));
+    assert_eq!(diagnostic.span, Span::new(5, 1, 4));
+    assert_eq!(diagnostic.notes.len(), 17);
+}
+
+#[test]
+fn test_malformed_macros_are_reported() {
+    let source = ".macro ADD a, a\n.endm\n.macro SET x\n@\\y\n.endm\n.macro NOP\n.endm\nNOP 1\n.endm\n.macro OPEN\n";
+    let errors: Vec<(AssembleError, usize)> = compile(String::from(source)).unwrap_err().into_iter()
+        .map(|diagnostic| (diagnostic.error, diagnostic.span.line))
+        .collect();
+
+    assert_eq!(errors, vec![
+        (AssembleError::Syntax(String::from("macro parameter `a` is declared twice")), 1),
+        (AssembleError::Syntax(String::from("macro `SET` has no parameter `y`")), 4),
+        (AssembleError::Syntax(String::from("macro `NOP` takes 0 argument(s) but 1 were given")), 8),
+        (AssembleError::Syntax(String::from("`.endm` without `.macro`")), 9),
+        (AssembleError::Syntax(String::from("unterminated macro `OPEN`, expected `.endm`")), 10),
+    ]);
+}
+
+fn assemble_files(files: &[(&str, &str)], include_paths: &[&str]) -> Result<Assembly, Vec<Diagnostic>> {
+    let loader: HashMap<PathBuf, String> = files.iter()
+        .map(|(path, source)| (PathBuf::from(path), String::from(*source)))
+        .collect();
+    let options = CompileOptions {
+        path: Some(PathBuf::from(files[0].0)),
+        include_paths: include_paths.iter().map(PathBuf::from).collect(),
+        ..CompileOptions::default()
+    };
+    assemble_with_loader(String::from(files[0].1), &options, &loader)
+}
+
+#[test]
+fn test_includes_resolve_next_to_includer_then_search_paths() {
+    let assembly = assemble_files(&[
+        ("src/Main.asm", "@1\n.include \"lib/push.asm\"\n@3\n"),
+        ("src/lib/push.asm", ".include bits.asm\n@2\n"),
+        ("std/bits.asm", "D=A\n"),
+    ], &["std"]).unwrap();
+
+    assert_eq!(assembly.to_hack(), compile(String::from("@1\nD=A\n@2\n@3\n")).unwrap());
+    assert_eq!(assembly.word_files, vec![0, 2, 1, 0]);
+    assert_eq!(assembly.files[2].name, "std/bits.asm");
+
+    let map = source_map(&assembly, "src/Main.asm");
+    assert!(map.contains("{\"address\": 1, \"file\": \"std/bits.asm\", \"line\": 1, \"column\": 1, \"length\": 3}"));
+    assert!(map.contains("{\"address\": 3, \"file\": \"src/Main.asm\", \"line\": 3, \"column\": 1, \"length\": 2}"));
+
+    let rows: Vec<String> = listing(&assembly).lines().skip(1).map(|row| row.split_whitespace().last().unwrap().to_string()).collect();
+    assert_eq!(rows, vec!["@1", "\"lib/push.asm\"", "bits.asm", "D=A", "@2", "@3"]);
+}
+
+#[test]
+fn test_include_cycles_are_reported_and_once_files_skipped() {
+    let diagnostics = assemble_files(&[
+        ("Main.asm", ".include a.asm\n"),
+        ("a.asm", ".include b.asm\n"),
+        ("b.asm", "@1\n.include a.asm\n"),
+    ], &[]).err().unwrap();
+    assert_eq!(diagnostics[0].error, AssembleError::Include(String::from("`a.asm` includes itself")));
+    assert_eq!((diagnostics[0].file.as_deref(), diagnostics[0].span.line), (Some("b.asm"), 2));
+    assert_eq!(diagnostics[0].notes[0], "note: include cycle a.asm -> b.asm -> a.asm");
+
+    let assembly = assemble_files(&[
+        ("Main.asm", ".include a.asm\n.include a.asm\n.include b.asm\n"),
+        ("a.asm", ".once\n@1\n"),
+        ("b.asm", ".once\n.include a.asm\n.include b.asm\n@2\n"),
+    ], &[]).unwrap();
+    assert_eq!(assembly.words.len(), 2);
+}
+
+#[test]
+fn test_errors_in_included_files_name_the_file() {
+    let diagnostic = assemble_files(&[
+        ("Main.asm", ".include lib.asm\n"),
+        ("lib.asm", "@1\nD=Q\n"),
+    ], &[]).err().unwrap().remove(0);
+    assert_eq!((diagnostic.file.as_deref(), diagnostic.span), (Some("lib.asm"), Span::new(2, 3, 1)));
+
+    let diagnostic = assemble_files(&[("Main.asm", "@1\n.include missing.asm\n")], &["std"]).err().unwrap().remove(0);
+    assert_eq!(diagnostic.error, AssembleError::Include(String::from("cannot find `missing.asm` to include")));
+    assert_eq!((diagnostic.file.as_deref(), diagnostic.span.line), (None, 2));
+    assert_eq!(diagnostic.notes, vec![String::from("note: looked for missing.asm, std/missing.asm")]);
+}
+
+#[test]
+fn test_errors_are_grouped_by_file() {
+    let diagnostics = assemble_files(&[
+        ("Main.asm", "@1\n.include a.asm\nD=Q\n.include b.asm\n@1\nD=Q\n"),
+        ("a.asm", "@1\n@1\nD=Q\n"),
+        ("b.asm", "D=Q\n"),
+    ], &[]).err().unwrap();
+
+    let positions: Vec<(Option<&str>, usize)> = diagnostics.iter()
+        .map(|diagnostic| (diagnostic.file.as_deref(), diagnostic.span.line))
+        .collect();
+    assert_eq!(positions, vec![(None, 3), (None, 6), (Some("a.asm"), 3), (Some("b.asm"), 1)]);
+}
+
+const DUMP_ASM: &str = "\
+.equ LEVEL 2
+.ifdef DEBUG
+    @1
+    .if LEVEL > 1 && (DEBUG & 2) == 2
+        @2
+    .else
+        @3
+    .endif
+.else
+    @4
+.endif
+.ifndef DEBUG
+    @5
+.endif
+.if 0
+    .if UNDEFINED  // never evaluated
+    .endif
+.endif
+";
+
+fn defining(defines: &[(&str, i64)]) -> CompileOptions {
+    let defines = defines.iter().map(|(name, value)| (String::from(*name), *value)).collect();
+    CompileOptions { defines, ..CompileOptions::default() }
+}
+
+#[test]
+fn test_conditionals_select_lines_by_defines() {
+    let words = |defines: &[(&str, i64)]| assemble(String::from(DUMP_ASM), &defining(defines)).unwrap().words;
+
+    assert_eq!(words(&[]), vec!["0000000000000100", "0000000000000101"]);
+    assert_eq!(words(&[("DEBUG", 1)]), vec!["0000000000000001", "0000000000000011"]);
+    assert_eq!(words(&[("DEBUG", 3)]), vec!["0000000000000001", "0000000000000010"]);
+}
+
+#[test]
+fn test_conditionals_in_macros_see_arguments() {
+    let source = ".macro REPEAT n\n.if \\n > 1\nD=D+1\nREPEAT \\n-1\n.endif\n.endm\nREPEAT 3\n";
+    assert_eq!(compile(String::from(source)).unwrap(), compile(String::from("D=D+1\nD=D+1\n")).unwrap());
+}
+
+#[test]
+fn test_malformed_conditionals_are_reported() {
+    let source = ".else\n.if 1\n.else\n.else\n.endif\n.endif\n.if MISSING\n.endif\n.ifdef\n.endif\n.if 1 +\n.endif\n.if 1\n";
+    let errors: Vec<(AssembleError, usize)> = compile(String::from(source)).unwrap_err().into_iter()
+        .map(|diagnostic| (diagnostic.error, diagnostic.span.line))
+        .collect();
+
+    assert_eq!(errors, vec![
+        (AssembleError::Syntax(String::from("`.else` without `.if`")), 1),
+        (AssembleError::Syntax(String::from("`.else` after `.else`, expected `.endif`")), 4),
+        (AssembleError::Syntax(String::from("`.endif` without `.if`")), 6),
+        (AssembleError::Syntax(String::from("`MISSING` is not defined, use `-D MISSING=VALUE` or `defined(MISSING)`")), 7),
+        (AssembleError::Syntax(String::from("expected a name after `.ifdef`")), 9),
+        (AssembleError::Syntax(String::from("expected a value in condition")), 11),
+        (AssembleError::Syntax(String::from("unterminated `.if`, expected `.endif`")), 13),
+    ]);
+}
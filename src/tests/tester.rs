@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use crate::parser::span::Span;
+use crate::tester::{compare, run_script, Files, Mismatch};
+use crate::tester::script::parse_script;
+use super::fixtures::*;
+
+struct MemoryFiles(HashMap<&'static str, &'static str>);
+
+impl Files for MemoryFiles {
+    fn read(&mut self, name: &str) -> Result<String, String> {
+        self.0.get(name).map(|text| String::from(*text)).ok_or_else(|| format!("no file `{}`", name))
+    }
+}
+
+fn max_files(cmp: &'static str) -> MemoryFiles {
+    MemoryFiles([("Max.asm", MAX_ASM), ("Max.hack", MAX_HACK), ("Max.cmp", cmp)].iter().cloned().collect())
+}
+
+#[test]
+fn test_script_output_matches_cmp() {
+    let commands = parse_script(MAX_TST).unwrap();
+    let run = run_script(&commands, &mut max_files(MAX_CMP)).unwrap();
+
+    assert_eq!(run.output, MAX_CMP);
+    assert_eq!(run.output_file, Some(String::from("Max.out")));
+    assert_eq!(compare(&run.output, run.compare_to.as_ref().unwrap()), Ok(()));
+}
+
+#[test]
+fn test_script_loads_hack_files() {
+    let script = MAX_TST.replace("load Max.asm", "load Max.hack");
+    let run = run_script(&parse_script(&script).unwrap(), &mut max_files(MAX_CMP)).unwrap();
+
+    assert_eq!(run.output, MAX_CMP);
+}
+
+#[test]
+fn test_compare_reports_first_mismatch() {
+    let expected = MAX_CMP.replace("|      22  |      47  |", "|      22  |      22  |");
+
+    assert_eq!(compare(MAX_CMP, &expected), Err(Mismatch {
+        line: 3,
+        expected: String::from("|      47  |      22  |      22  |"),
+        actual: String::from("|      47  |      22  |      47  |"),
+    }));
+}
+
+#[test]
+fn test_compare_treats_star_as_wildcard() {
+    let expected = MAX_CMP.replace("|      32  |\n", "|  ******  |\n");
+
+    assert_eq!(compare(MAX_CMP, &expected), Ok(()));
+}
+
+#[test]
+fn test_script_formats_binary_and_hex() {
+    let script = "load Max.asm, output-list RAM[0]%B1.16.1 RAM[0]%X1.4.1 D%D1.3.1; set RAM[0] -1, set D %X0A, output;";
+    let run = run_script(&parse_script(script).unwrap(), &mut max_files(MAX_CMP)).unwrap();
+
+    assert_eq!(run.output, "|      RAM[0]      |RAM[0]|  D  |\n| 1111111111111111 | FFFF |  10 |\n");
+}
+
+#[test]
+fn test_script_reports_unknown_command() {
+    let diagnostic = parse_script("load Max.asm,\n  tickle;\n").unwrap_err();
+
+    assert_eq!(diagnostic.span, Span::new(2, 3, 6));
+    assert_eq!(diagnostic.error.to_string(), "unknown command `tickle`");
+}
@@ -0,0 +1,179 @@
+use std::env;
+use std::fs;
+use crate::cli::{run, Console, EXIT_SUCCESS};
+use crate::disassembler::read_words;
+use crate::emulator::{Cpu, RunResult};
+use crate::parser::compiler::compile;
+use crate::vm::parser::{parse_vm, Segment, VmCommand};
+use crate::vm::{translate, TranslateOptions, VmFile};
+
+fn run_vm(files: &[(&str, &str)], bootstrap: bool) -> Cpu {
+    let files: Vec<VmFile> = files.iter()
+        .map(|(name, source)| VmFile { name: String::from(*name), source: String::from(*source) })
+        .collect();
+    let asm = translate(&files, &TranslateOptions { bootstrap }).unwrap();
+    let program = read_words(&compile(asm).unwrap()).unwrap();
+
+    let mut cpu = Cpu::new();
+    cpu.load(&program).unwrap();
+    if !bootstrap {
+        cpu.ram_mut()[0] = 256;
+    }
+    cpu.run(100_000);
+    cpu
+}
+
+#[test]
+fn test_vm_simple_add() {
+    let cpu = run_vm(&[("SimpleAdd", "push constant 7\npush constant 8\nadd\n")], false);
+
+    assert_eq!(cpu.ram()[0], 257);
+    assert_eq!(cpu.ram()[256], 15);
+}
+
+#[test]
+fn test_vm_stack_operations() {
+    let source = "\
+push constant 17
+push constant 17
+eq
+push constant 892
+push constant 891
+lt
+push constant 32767
+push constant 32766
+gt
+push constant 57
+push constant 31
+and
+push constant 82
+push constant 112
+or
+push constant 10
+neg
+not
+";
+    let cpu = run_vm(&[("StackTest", source)], false);
+
+    assert_eq!(cpu.ram()[0], 262);
+    assert_eq!(&cpu.ram()[256..262], &[0xffff, 0, 0xffff, 25, 114, 9]);
+}
+
+#[test]
+fn test_vm_memory_segments() {
+    let source = "\
+push constant 3030
+pop pointer 0
+push constant 3040
+pop pointer 1
+push constant 32
+pop this 2
+push constant 46
+pop that 6
+push constant 510
+pop temp 6
+push constant 7
+pop static 3
+push this 2
+push that 6
+add
+push temp 6
+sub
+push static 3
+add
+";
+    let cpu = run_vm(&[("Segments", source)], false);
+
+    assert_eq!(cpu.ram()[3], 3030);
+    assert_eq!(cpu.ram()[4], 3040);
+    assert_eq!(cpu.ram()[3032], 32);
+    assert_eq!(cpu.ram()[3046], 46);
+    assert_eq!(cpu.ram()[11], 510);
+    assert_eq!(cpu.ram()[256], (32 + 46 - 510 + 7) as u16);
+}
+
+#[test]
+fn test_vm_calls_functions_with_bootstrap() {
+    let sys = "\
+function Sys.init 0
+push constant 4
+push constant 5
+call Main.mult 2
+pop static 0
+label END
+goto END
+";
+    let main = "\
+function Main.mult 1
+label LOOP
+push argument 1
+if-goto BODY
+push local 0
+return
+label BODY
+push local 0
+push argument 0
+add
+pop local 0
+push argument 1
+push constant 1
+sub
+pop argument 1
+goto LOOP
+";
+    let cpu = run_vm(&[("Main", main), ("Sys", sys)], true);
+
+    assert_eq!(cpu.ram()[16], 20);
+    assert_eq!(cpu.ram()[0], 261);
+}
+
+#[test]
+fn test_vm_scopes_labels_to_functions() {
+    let files = vec![VmFile {
+        name: String::from("Main"),
+        source: String::from("function Main.a 0\nlabel LOOP\ngoto LOOP\nfunction Main.b 0\nlabel LOOP\ngoto LOOP\n"),
+    }];
+    let asm = translate(&files, &TranslateOptions { bootstrap: false }).unwrap();
+
+    assert!(asm.contains("(Main.a$LOOP)"));
+    assert!(asm.contains("(Main.b$LOOP)"));
+    assert!(compile(asm).is_ok());
+}
+
+#[test]
+fn test_vm_parses_commands_with_comments() {
+    let commands = parse_vm("// header\n  push local 2 // trailing\ncall Foo.bar 3\n").unwrap();
+
+    assert_eq!(commands[0].node, VmCommand::Push(Segment::Local, 2));
+    assert_eq!(commands[0].span.line, 2);
+    assert_eq!(commands[0].span.column, 3);
+    assert_eq!(commands[1].node, VmCommand::Call(String::from("Foo.bar"), 3));
+}
+
+#[test]
+fn test_vm_reports_every_bad_line() {
+    let diagnostics = parse_vm("push nowhere 1\npop constant 0\npush temp 8\nfrobnicate\n").unwrap_err();
+
+    let lines: Vec<(usize, usize)> = diagnostics.iter().map(|d| (d.span.line, d.span.column)).collect();
+    assert_eq!(lines, vec![(1, 6), (2, 5), (3, 11), (4, 1)]);
+}
+
+#[test]
+fn test_vm_cli_translates_directory() {
+    let dir = env::temp_dir().join(format!("assembler_rust_vm_{}", std::process::id())).join("Prog");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("Sys.vm"), "function Sys.init 0\npush constant 42\npop static 0\nlabel END\ngoto END\n").unwrap();
+    fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+    let args = vec![String::from("vm"), dir.to_str().unwrap().to_string(), String::from("--hack")];
+    let (mut stdin, mut stdout, mut stderr) = (&b""[..], vec![], vec![]);
+    let status = run(&args, &mut Console { stdin: &mut stdin, stdout: &mut stdout, stderr: &mut stderr });
+    assert_eq!(status, EXIT_SUCCESS, "{}", String::from_utf8_lossy(&stderr));
+
+    let program = read_words(&fs::read_to_string(dir.join("Prog.hack")).unwrap()).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load(&program).unwrap();
+    assert_eq!(cpu.run(10_000), RunResult::Halted);
+    assert_eq!(cpu.ram()[16], 42);
+}
@@ -0,0 +1,250 @@
+pub mod parser;
+
+use crate::parser::error::Diagnostic;
+use crate::vm::parser::{parse_vm, Segment, VmCommand};
+
+pub struct VmFile {
+    // File stem, used to name the `static` segment of the file (`Main.0`).
+    pub name: String,
+    pub source: String,
+}
+
+pub struct TranslateOptions {
+    // Emit `SP=256; call Sys.init 0` before the translated files.
+    pub bootstrap: bool,
+}
+
+impl Default for TranslateOptions {
+    fn default() -> Self {
+        TranslateOptions { bootstrap: true }
+    }
+}
+
+// Diagnostics are paired with the index of the file they belong to.
+pub fn translate(files: &[VmFile], options: &TranslateOptions) -> Result<String, Vec<(usize, Diagnostic)>> {
+    let mut writer = AsmWriter::new();
+    let mut errors = vec![];
+
+    if options.bootstrap {
+        writer.bootstrap();
+    }
+
+    for (index, file) in files.iter().enumerate() {
+        match parse_vm(&file.source) {
+            Ok(commands) => {
+                writer.file = file.name.clone();
+                writer.function = String::new();
+                for command in commands {
+                    writer.command(&command.node);
+                }
+            },
+            Err(diagnostics) => errors.extend(diagnostics.into_iter().map(|diagnostic| (index, diagnostic))),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(writer.buffer)
+}
+
+struct AsmWriter {
+    buffer: String,
+    file: String,
+    function: String,
+    labels: usize,
+}
+
+impl AsmWriter {
+    fn new() -> AsmWriter {
+        AsmWriter { buffer: String::new(), file: String::new(), function: String::new(), labels: 0 }
+    }
+
+    fn bootstrap(&mut self) {
+        self.emit("// bootstrap");
+        self.emit_all(&["@256", "D=A", "@SP", "M=D"]);
+        self.call("Sys.init", 0);
+    }
+
+    fn command(&mut self, command: &VmCommand) {
+        self.emit(&format!("// {}", describe(command)));
+
+        match command {
+            VmCommand::Arithmetic(operation) => self.arithmetic(operation),
+            VmCommand::Push(segment, index) => self.push(*segment, *index),
+            VmCommand::Pop(segment, index) => self.pop(*segment, *index),
+            VmCommand::Label(label) => {
+                let label = self.scoped(label);
+                self.emit(&format!("({})", label));
+            },
+            VmCommand::Goto(label) => {
+                let label = self.scoped(label);
+                self.emit_all(&[&format!("@{}", label), "0;JMP"]);
+            },
+            VmCommand::IfGoto(label) => {
+                let label = self.scoped(label);
+                self.emit_all(&["@SP", "AM=M-1", "D=M", &format!("@{}", label), "D;JNE"]);
+            },
+            VmCommand::Function(name, locals) => {
+                self.function = name.clone();
+                self.emit(&format!("({})", name));
+                for _ in 0..*locals {
+                    self.emit_all(&["@SP", "A=M", "M=0", "@SP", "M=M+1"]);
+                }
+            },
+            VmCommand::Call(name, arguments) => self.call(name, *arguments),
+            VmCommand::Return => self.ret(),
+        }
+    }
+
+    fn arithmetic(&mut self, operation: &str) {
+        match operation {
+            "neg" => self.emit_all(&["@SP", "A=M-1", "M=-M"]),
+            "not" => self.emit_all(&["@SP", "A=M-1", "M=!M"]),
+            "add" => self.binary("M=D+M"),
+            "sub" => self.binary("M=M-D"),
+            "and" => self.binary("M=D&M"),
+            "or" => self.binary("M=D|M"),
+            _ => {
+                let jump = match operation {
+                    "eq" => "D;JEQ",
+                    "gt" => "D;JGT",
+                    _ => "D;JLT",
+                };
+                let id = self.next_label();
+                self.emit_all(&["@SP", "AM=M-1", "D=M", "A=A-1", "D=M-D"]);
+                self.emit_all(&[&format!("@VM_TRUE.{}", id), jump]);
+                self.emit_all(&["@SP", "A=M-1", "M=0", &format!("@VM_END.{}", id), "0;JMP"]);
+                self.emit_all(&[&format!("(VM_TRUE.{})", id), "@SP", "A=M-1", "M=-1"]);
+                self.emit(&format!("(VM_END.{})", id));
+            }
+        }
+    }
+
+    // Pops y into D and leaves A pointing at x, so `operation` writes x op y in place.
+    fn binary(&mut self, operation: &str) {
+        self.emit_all(&["@SP", "AM=M-1", "D=M", "A=A-1", operation]);
+    }
+
+    fn push(&mut self, segment: Segment, index: u16) {
+        match segment {
+            Segment::Constant => self.emit_all(&[&format!("@{}", index), "D=A"]),
+            Segment::Local | Segment::Argument | Segment::This | Segment::That => {
+                self.emit_all(&[&format!("@{}", index), "D=A", base(segment), "A=D+M", "D=M"]);
+            },
+            _ => {
+                let address = self.fixed_address(segment, index);
+                self.emit_all(&[&address, "D=M"]);
+            }
+        }
+        self.push_d();
+    }
+
+    fn pop(&mut self, segment: Segment, index: u16) {
+        match segment {
+            Segment::Local | Segment::Argument | Segment::This | Segment::That => {
+                self.emit_all(&[&format!("@{}", index), "D=A", base(segment), "D=D+M", "@R13", "M=D"]);
+                self.emit_all(&["@SP", "AM=M-1", "D=M", "@R13", "A=M", "M=D"]);
+            },
+            _ => {
+                let address = self.fixed_address(segment, index);
+                self.emit_all(&["@SP", "AM=M-1", "D=M", &address, "M=D"]);
+            }
+        }
+    }
+
+    fn call(&mut self, name: &str, arguments: u16) {
+        let id = self.next_label();
+        let return_label = format!("{}$ret.{}", self.caller(), id);
+
+        self.emit_all(&[&format!("@{}", return_label), "D=A"]);
+        self.push_d();
+        for pointer in &["@LCL", "@ARG", "@THIS", "@THAT"] {
+            self.emit_all(&[pointer, "D=M"]);
+            self.push_d();
+        }
+        self.emit_all(&["@SP", "D=M", "@5", "D=D-A", &format!("@{}", arguments), "D=D-A", "@ARG", "M=D"]);
+        self.emit_all(&["@SP", "D=M", "@LCL", "M=D"]);
+        self.emit_all(&[&format!("@{}", name), "0;JMP", &format!("({})", return_label)]);
+    }
+
+    // R13 holds the frame (LCL of the returning function), R14 the return address.
+    fn ret(&mut self) {
+        self.emit_all(&["@LCL", "D=M", "@R13", "M=D"]);
+        self.emit_all(&["@5", "A=D-A", "D=M", "@R14", "M=D"]);
+        self.emit_all(&["@SP", "AM=M-1", "D=M", "@ARG", "A=M", "M=D"]);
+        self.emit_all(&["@ARG", "D=M+1", "@SP", "M=D"]);
+        for pointer in &["@THAT", "@THIS", "@ARG", "@LCL"] {
+            self.emit_all(&["@R13", "AM=M-1", "D=M", pointer, "M=D"]);
+        }
+        self.emit_all(&["@R14", "A=M", "0;JMP"]);
+    }
+
+    fn push_d(&mut self) {
+        self.emit_all(&["@SP", "A=M", "M=D", "@SP", "M=M+1"]);
+    }
+
+    fn fixed_address(&self, segment: Segment, index: u16) -> String {
+        match segment {
+            Segment::Pointer => format!("@R{}", 3 + index),
+            Segment::Temp => format!("@R{}", 5 + index),
+            _ => format!("@{}.{}", self.file, index),
+        }
+    }
+
+    fn scoped(&self, label: &str) -> String {
+        format!("{}${}", self.caller(), label)
+    }
+
+    // Code outside any function (single-file tests of project 7) is scoped to
+    // the file, the bootstrap code to `Bootstrap`.
+    fn caller(&self) -> &str {
+        if !self.function.is_empty() {
+            &self.function
+        } else if !self.file.is_empty() {
+            &self.file
+        } else {
+            "Bootstrap"
+        }
+    }
+
+    fn next_label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels
+    }
+
+    fn emit(&mut self, line: &str) {
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+    }
+
+    fn emit_all(&mut self, lines: &[&str]) {
+        for line in lines {
+            self.emit(line);
+        }
+    }
+}
+
+fn base(segment: Segment) -> &'static str {
+    match segment {
+        Segment::Local => "@LCL",
+        Segment::Argument => "@ARG",
+        Segment::This => "@THIS",
+        _ => "@THAT",
+    }
+}
+
+fn describe(command: &VmCommand) -> String {
+    let segment = |segment: &Segment| format!("{:?}", segment).to_lowercase();
+    match command {
+        VmCommand::Arithmetic(operation) => operation.clone(),
+        VmCommand::Push(s, index) => format!("push {} {}", segment(s), index),
+        VmCommand::Pop(s, index) => format!("pop {} {}", segment(s), index),
+        VmCommand::Label(label) => format!("label {}", label),
+        VmCommand::Goto(label) => format!("goto {}", label),
+        VmCommand::IfGoto(label) => format!("if-goto {}", label),
+        VmCommand::Function(name, locals) => format!("function {} {}", name, locals),
+        VmCommand::Call(name, arguments) => format!("call {} {}", name, arguments),
+        VmCommand::Return => String::from("return"),
+    }
+}
@@ -0,0 +1,147 @@
+use crate::parser::error::{AssembleError, Diagnostic};
+use crate::parser::span::{Span, Spanned};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment {
+    Constant,
+    Local,
+    Argument,
+    This,
+    That,
+    Pointer,
+    Temp,
+    Static,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmCommand {
+    Arithmetic(String),
+    Push(Segment, u16),
+    Pop(Segment, u16),
+    Label(String),
+    Goto(String),
+    IfGoto(String),
+    Function(String, u16),
+    Call(String, u16),
+    Return,
+}
+
+const ARITHMETIC: [&str; 9] = ["add", "sub", "neg", "eq", "gt", "lt", "and", "or", "not"];
+
+pub fn parse_vm(source: &str) -> Result<Vec<Spanned<VmCommand>>, Vec<Diagnostic>> {
+    let mut commands = vec![];
+    let mut diagnostics = vec![];
+
+    for (index, line) in source.lines().enumerate() {
+        let code = match line.find("//") {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let words = split_words(code, index + 1);
+        if words.is_empty() {
+            continue;
+        }
+
+        let span = words[0].span.to(words[words.len() - 1].span);
+        match parse_command(&words) {
+            Ok(command) => commands.push(Spanned::new(command, span)),
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+    Ok(commands)
+}
+
+fn parse_command(words: &[Spanned<&str>]) -> Result<VmCommand, Diagnostic> {
+    let name = words[0].node;
+    let arity = match name {
+        _ if ARITHMETIC.contains(&name) => 0,
+        "return" => 0,
+        "label" | "goto" | "if-goto" => 1,
+        "push" | "pop" | "function" | "call" => 2,
+        _ => return Err(syntax_error(format!("unknown VM command `{}`", name), words[0].span)),
+    };
+    if words.len() != arity + 1 {
+        let message = format!("`{}` takes {} argument(s), found {}", name, arity, words.len() - 1);
+        return Err(syntax_error(message, words[0].span));
+    }
+
+    Ok(match name {
+        "return" => VmCommand::Return,
+        "label" => VmCommand::Label(String::from(words[1].node)),
+        "goto" => VmCommand::Goto(String::from(words[1].node)),
+        "if-goto" => VmCommand::IfGoto(String::from(words[1].node)),
+        "function" => VmCommand::Function(String::from(words[1].node), parse_index(&words[2])?),
+        "call" => VmCommand::Call(String::from(words[1].node), parse_index(&words[2])?),
+        "push" | "pop" => {
+            let segment = parse_segment(&words[1])?;
+            let index = parse_index(&words[2])?;
+            let limit = match segment {
+                Segment::Pointer => 2,
+                Segment::Temp => 8,
+                Segment::Static => 240,
+                _ => 32768,
+            };
+            if index >= limit {
+                return Err(Diagnostic::new(AssembleError::ConstantOutOfRange(String::from(words[2].node)), words[2].span));
+            }
+            if name == "pop" && segment == Segment::Constant {
+                return Err(syntax_error(String::from("cannot pop into the `constant` segment"), words[1].span));
+            }
+
+            if name == "push" { VmCommand::Push(segment, index) } else { VmCommand::Pop(segment, index) }
+        },
+        _ => VmCommand::Arithmetic(String::from(name)),
+    })
+}
+
+fn parse_segment(word: &Spanned<&str>) -> Result<Segment, Diagnostic> {
+    match word.node {
+        "constant" => Ok(Segment::Constant),
+        "local" => Ok(Segment::Local),
+        "argument" => Ok(Segment::Argument),
+        "this" => Ok(Segment::This),
+        "that" => Ok(Segment::That),
+        "pointer" => Ok(Segment::Pointer),
+        "temp" => Ok(Segment::Temp),
+        "static" => Ok(Segment::Static),
+        _ => Err(syntax_error(format!("unknown segment `{}`", word.node), word.span)),
+    }
+}
+
+fn parse_index(word: &Spanned<&str>) -> Result<u16, Diagnostic> {
+    if !word.node.chars().all(|c| c.is_ascii_digit()) {
+        return Err(syntax_error(format!("expected a non-negative number, found `{}`", word.node), word.span));
+    }
+    word.node.parse()
+        .ok()
+        .filter(|index| *index < 32768)
+        .ok_or_else(|| Diagnostic::new(AssembleError::ConstantOutOfRange(String::from(word.node)), word.span))
+}
+
+fn syntax_error(message: String, span: Span) -> Diagnostic {
+    Diagnostic::new(AssembleError::Syntax(message), span)
+}
+
+fn split_words(code: &str, line: usize) -> Vec<Spanned<&str>> {
+    let mut words = vec![];
+    let mut start: Option<usize> = None;
+
+    for (index, c) in code.char_indices().chain(std::iter::once((code.len(), ' '))) {
+        match (c.is_whitespace(), start) {
+            (true, Some(begin)) => {
+                let column = code[..begin].chars().count() + 1;
+                let word = &code[begin..index];
+                words.push(Spanned::new(word, Span::new(line, column, word.chars().count())));
+                start = None;
+            },
+            (false, None) => start = Some(index),
+            _ => {}
+        }
+    }
+
+    words
+}